@@ -0,0 +1,34 @@
+//! Evaluate arbitrary input, and check that constant folding never changes
+//! the result of evaluation.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use oxur::parser::interp::{Env, Interpreter};
+use oxur::parser::{optimize, sexp};
+
+fuzz_target!(|data: &[u8]| {
+    let src = match std::str::from_utf8(data) {
+        Ok(src) => src,
+        Err(_) => return,
+    };
+    if let Ok(expr) = sexp::parse_from_str(src) {
+        let expected = sexp::eval_expression(expr.clone());
+        let optimized = optimize::optimize(expr.clone());
+        assert_eq!(sexp::eval_expression(optimized.clone()), expected);
+
+        // Closures are compared by how they print, since two evaluations
+        // never return the same one.
+        let interpreter = Interpreter::default();
+        let eval = |expr| {
+            interpreter
+                .eval(expr, &Env::default())
+                .map(|value| value.to_string())
+        };
+        let expected = eval(&expr);
+        // A folded tree is shallower, so it may finish where the original
+        // ran out of depth.
+        if !matches!(&expected, Err(error) if error.message.starts_with("evaluation nested")) {
+            assert_eq!(eval(&optimized), expected);
+        }
+    }
+});
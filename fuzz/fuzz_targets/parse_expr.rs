@@ -0,0 +1,22 @@
+//! Parse arbitrary input, and check that anything which parses survives a
+//! round trip through the printer unchanged, in every layout.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use oxur::parser::print::{Layout, Printer};
+use oxur::parser::sexp;
+
+fuzz_target!(|data: &[u8]| {
+    let src = match std::str::from_utf8(data) {
+        Ok(src) => src,
+        Err(_) => return,
+    };
+    if let Ok(expr) = sexp::parse_from_str(src) {
+        let printed = expr.to_string();
+        assert_eq!(sexp::parse_from_str(&printed).as_ref(), Ok(&expr));
+        for &layout in &[Layout::Fit, Layout::Compact, Layout::Expanded] {
+            let pretty = Printer { width: 20, layout }.print(&expr);
+            assert_eq!(sexp::parse_from_str(&pretty).as_ref(), Ok(&expr));
+        }
+    }
+});
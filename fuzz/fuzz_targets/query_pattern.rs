@@ -0,0 +1,19 @@
+//! Parse arbitrary input as a query pattern, and match it against a fixed
+//! expression.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use oxur::parser::{query, sexp};
+
+const SOURCE: &str = "((if (= (+ 3 (/ 9 3)) (* 2 3)) * /) 456 '(:a #t (- 1 1)))";
+
+fuzz_target!(|data: &[u8]| {
+    let src = match std::str::from_utf8(data) {
+        Ok(src) => src,
+        Err(_) => return,
+    };
+    if let Ok(pattern) = query::pattern_from_str(src) {
+        let expr = sexp::parse_from_str(SOURCE).unwrap();
+        let _ = query::query(&pattern, &expr);
+    }
+});
@@ -40,7 +40,7 @@ impl Display for Error {
         use self::Error::*;
 
         match self {
-            IncorrectUsage => write!(f, "Usage: dump-syntax path/to/filename.rs"),
+            IncorrectUsage => write!(f, "Usage: dump-syntax [--no-color] path/to/filename.rs"),
             ReadFile(error) => write!(f, "Unable to read file: {}", error),
             ParseFile {
                 error,
@@ -16,8 +16,12 @@ fn try_main() -> Result<(), dump::Error> {
     let mut args = env::args_os();
     let _ = args.next(); // executable name
 
-    let filepath = match (args.next(), args.next()) {
-        (Some(arg), None) => PathBuf::from(arg),
+    let filepath = match (args.next(), args.next(), args.next()) {
+        (Some(arg), None, None) => PathBuf::from(arg),
+        (Some(flag), Some(arg), None) if flag == "--no-color" => {
+            colored::control::set_override(false);
+            PathBuf::from(arg)
+        }
         _ => return Err(dump::Error::IncorrectUsage),
     };
 
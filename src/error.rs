@@ -0,0 +1,366 @@
+//! The error type shared by the parser, the evaluator, and the command line
+//! drivers.
+//!
+//! Every error carries a stable code, a severity, and (when it can be tied to
+//! the input) a byte span, so that it can be rendered against the source in
+//! the same rustc style as `ast::dump`:
+//!
+//! ```text
+//! error[E0001]: expected ')', found end of input
+//!   --> example.oxr:1:5
+//!    |
+//!  1 | (+ 1
+//!    |     ^ expected ')', found end of input
+//!    = note: while parsing closing paren
+//! ```
+//!
+//! Rendering needs the `std` feature; without it errors still carry all of the
+//! above and print in their one-line `Display` form.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+#[cfg(feature = "std")]
+use colored::Colorize;
+use nom::error::{VerboseError, VerboseErrorKind};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ErrorCode {
+    /// The input does not match the grammar.
+    Syntax,
+    /// A complete form was parsed but more input follows it.
+    TrailingInput,
+    /// The input ended in the middle of a form.
+    Incomplete,
+    /// The input is too large or too deeply nested to parse safely.
+    LimitExceeded,
+    /// A well-formed expression could not be evaluated.
+    Eval,
+    /// A lint objected to a form; see `parser::lint`.
+    Lint,
+    /// A symbol refers to no `let` or `fn` binding in scope.
+    UnboundSymbol,
+    /// A `fn` names the same parameter twice.
+    DuplicateParameter,
+    /// A binding hides another one of the same name.
+    Shadowing,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::Syntax => "E0001",
+            ErrorCode::TrailingInput => "E0002",
+            ErrorCode::Incomplete => "E0003",
+            ErrorCode::LimitExceeded => "E0004",
+            ErrorCode::Eval => "E0100",
+            ErrorCode::Lint => "E0200",
+            ErrorCode::UnboundSymbol => "E0300",
+            ErrorCode::DuplicateParameter => "E0301",
+            ErrorCode::Shadowing => "E0302",
+        }
+    }
+}
+
+/// A half-open range of byte offsets into the source.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct OxurError {
+    pub code: ErrorCode,
+    pub severity: Severity,
+    pub span: Option<Span>,
+    pub message: String,
+    pub notes: Vec<String>,
+}
+
+pub type Result<T> = core::result::Result<T, OxurError>;
+
+impl OxurError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        OxurError {
+            code,
+            severity: Severity::Error,
+            span: None,
+            message: message.into(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_span(mut self, start: usize, end: usize) -> Self {
+        self.span = Some(Span { start, end });
+        self
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Convert a nom parse failure on `src` into an error pointing at the
+    /// innermost failure, keeping any enclosing contexts as notes.
+    pub fn from_nom(src: &str, err: nom::Err<VerboseError<&str>>) -> Self {
+        let err = match err {
+            nom::Err::Error(err) | nom::Err::Failure(err) => err,
+            nom::Err::Incomplete(_) => {
+                return OxurError::new(ErrorCode::Incomplete, "incomplete input")
+                    .with_span(src.len(), src.len())
+            }
+        };
+
+        let mut error = OxurError::new(ErrorCode::Syntax, "invalid syntax");
+        let mut primary = (0, false);
+        for (i, (rest, kind)) in err.errors.iter().enumerate() {
+            let offset = src.len() - rest.len();
+            if i == 0 {
+                // A bare nom error means every alternative failed at this
+                // point; contexts entered at the same offset are just the
+                // alternatives that were tried, so they make poor notes.
+                primary = (offset, matches!(kind, VerboseErrorKind::Nom(_)));
+                error.message = match kind {
+                    VerboseErrorKind::Char(c) => match rest.chars().next() {
+                        Some(found) => format!("expected '{}', found '{}'", c, found),
+                        None => format!("expected '{}', found end of input", c),
+                    },
+                    VerboseErrorKind::Context(context) => format!("invalid {}", context),
+                    VerboseErrorKind::Nom(_) if rest.is_empty() => {
+                        "unexpected end of input".to_string()
+                    }
+                    VerboseErrorKind::Nom(_) => "unexpected input".to_string(),
+                };
+                let end = offset + rest.chars().next().map_or(0, char::len_utf8);
+                error = error.with_span(offset, end);
+            } else if let VerboseErrorKind::Context(context) = kind {
+                if primary == (offset, true) {
+                    continue;
+                }
+                error = error.with_note(format!("while parsing {}", context));
+            }
+        }
+        error
+    }
+
+    /// Render the error against the source it was produced from, including
+    /// colors. Errors without a span fall back to the one-line form.
+    #[cfg(feature = "std")]
+    pub fn render(&self, filename: &str, src: &str) -> String {
+        let header = format!(
+            "{}{}",
+            match self.severity {
+                Severity::Error => format!("error[{}]", self.code.as_str()).red().bold(),
+                Severity::Warning => format!("warning[{}]", self.code.as_str()).yellow().bold(),
+            },
+            format!(": {}", self.message).bold(),
+        );
+        let span = match self.span {
+            Some(span) => span,
+            None => return format!("{}\n{}", header, self.render_notes("")),
+        };
+
+        let start = span.start.min(src.len());
+        let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = src[start..].find('\n').map_or(src.len(), |i| start + i);
+        let linenum = src[..start].matches('\n').count() + 1;
+        let column = src[line_start..start].chars().count();
+        let width = src[start..span.end.min(line_end).max(start)]
+            .chars()
+            .count()
+            .max(1);
+        let indent = " ".repeat(linenum.to_string().len());
+
+        format!(
+            "{header}\n\
+             {indent}{arrow} {filename}:{linenum}:{colnum}\n\
+             {indent} {pipe}\n\
+             {label} {pipe} {code}\n\
+             {indent} {pipe} {offset}{underline} {message}\n\
+             {notes}",
+            header = header,
+            indent = indent,
+            arrow = "-->".blue().bold(),
+            filename = filename,
+            linenum = linenum,
+            colnum = column + 1,
+            pipe = "|".blue().bold(),
+            label = linenum.to_string().blue().bold(),
+            code = src[line_start..line_end].trim_end(),
+            offset = " ".repeat(column),
+            underline = "^".repeat(width).red().bold(),
+            message = self.message.red(),
+            notes = self.render_notes(&indent),
+        )
+    }
+
+    #[cfg(feature = "std")]
+    fn render_notes(&self, indent: &str) -> String {
+        self.notes
+            .iter()
+            .map(|note| format!("{} {} note: {}\n", indent, "=".blue().bold(), note))
+            .collect()
+    }
+}
+
+impl Display for OxurError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}[{}]: {}", label, self.code.as_str(), self.message)?;
+        if let Some(span) = self.span {
+            write!(f, " at offset {}", span.start)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OxurError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use nom::error::ErrorKind;
+
+    fn verbose<'a>(errors: Vec<(&'a str, VerboseErrorKind)>) -> nom::Err<VerboseError<&'a str>> {
+        nom::Err::Failure(VerboseError { errors })
+    }
+
+    #[test]
+    fn nom_errors_point_at_the_innermost_failure() {
+        let src = "(+ 1 ]";
+        let error = OxurError::from_nom(
+            src,
+            verbose(vec![
+                (&src[5..], VerboseErrorKind::Char(')')),
+                (&src[5..], VerboseErrorKind::Context("closing paren")),
+                (src, VerboseErrorKind::Context("list")),
+            ]),
+        );
+        assert_eq!(
+            error,
+            OxurError {
+                code: ErrorCode::Syntax,
+                severity: Severity::Error,
+                span: Some(Span { start: 5, end: 6 }),
+                message: "expected ')', found ']'".to_string(),
+                notes: vec![
+                    "while parsing closing paren".to_string(),
+                    "while parsing list".to_string(),
+                ],
+            }
+        );
+
+        let error = OxurError::from_nom("(+ 1", verbose(vec![("", VerboseErrorKind::Char(')'))]));
+        assert_eq!(error.message, "expected ')', found end of input");
+        assert_eq!(error.span, Some(Span { start: 4, end: 4 }));
+    }
+
+    #[test]
+    fn nom_errors_name_the_context_that_failed() {
+        let src = "(fn (x x) x)";
+        let error = OxurError::from_nom(
+            src,
+            verbose(vec![
+                (&src[7..], VerboseErrorKind::Context("duplicate parameter")),
+                (src, VerboseErrorKind::Context("fn")),
+            ]),
+        );
+        assert_eq!(error.message, "invalid duplicate parameter");
+        assert_eq!(error.span, Some(Span { start: 7, end: 8 }));
+        assert_eq!(error.notes, vec!["while parsing fn"]);
+    }
+
+    #[test]
+    fn alternatives_tried_at_the_failure_are_not_notes() {
+        let src = "(+ 1 é)";
+        let error = OxurError::from_nom(
+            src,
+            verbose(vec![
+                (&src[5..], VerboseErrorKind::Nom(ErrorKind::Alt)),
+                (&src[5..], VerboseErrorKind::Context("atom")),
+                (&src[5..], VerboseErrorKind::Context("list")),
+                (src, VerboseErrorKind::Context("expression")),
+            ]),
+        );
+        assert_eq!(error.message, "unexpected input");
+        // the span covers the whole of the first character
+        assert_eq!(error.span, Some(Span { start: 5, end: 7 }));
+        assert_eq!(error.notes, vec!["while parsing expression"]);
+
+        let error = OxurError::from_nom(
+            src,
+            verbose(vec![("", VerboseErrorKind::Nom(ErrorKind::Eof))]),
+        );
+        assert_eq!(error.message, "unexpected end of input");
+    }
+
+    #[test]
+    fn incomplete_input_points_at_the_end() {
+        let error = OxurError::from_nom("(+ 1", nom::Err::Incomplete(nom::Needed::Unknown));
+        assert_eq!(error.code, ErrorCode::Incomplete);
+        assert_eq!(error.span, Some(Span { start: 4, end: 4 }));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn render_points_at_the_span() {
+        colored::control::set_override(false);
+        let src = "(let ((x 1))\n  (+ \"héllo\" y))";
+        let start = src.find('y').unwrap();
+        let error = OxurError::new(ErrorCode::UnboundSymbol, "unbound symbol `y`")
+            .with_span(start, start + 1)
+            .with_note("add a binding for `y`");
+        // the column counts characters, not bytes
+        assert_eq!(
+            error.render("test.oxr", src),
+            concat!(
+                "error[E0300]: unbound symbol `y`\n",
+                " --> test.oxr:2:14\n",
+                "  |\n",
+                "2 |   (+ \"héllo\" y))\n",
+                "  |              ^ unbound symbol `y`\n",
+                "  = note: add a binding for `y`\n",
+            )
+        );
+
+        let src = format!("{}(+ 1 2) (- 3", "\n".repeat(11));
+        let error = OxurError::new(ErrorCode::Incomplete, "unexpected end of input")
+            .with_span(src.len(), src.len())
+            .with_severity(Severity::Warning);
+        assert_eq!(
+            error.render("test.oxr", &src),
+            concat!(
+                "warning[E0003]: unexpected end of input\n",
+                "  --> test.oxr:12:13\n",
+                "   |\n",
+                "12 | (+ 1 2) (- 3\n",
+                "   |             ^ unexpected end of input\n",
+            )
+        );
+
+        let error = OxurError::new(ErrorCode::Eval, "no span").with_note("a note");
+        assert_eq!(
+            error.render("test.oxr", ""),
+            "error[E0100]: no span\n = note: a note\n"
+        );
+    }
+}
@@ -1,2 +1,9 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
 pub mod ast;
+pub mod error;
 pub mod parser;
+pub mod spec;
@@ -1,3 +1,362 @@
+use std::env;
+use std::ffi::OsString;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+
+use oxur::error::{ErrorCode, OxurError, Severity};
+use oxur::parser::interp::{Env, Interpreter};
+use oxur::parser::limits::Parser;
+use oxur::parser::lint::{Level, Linter};
+use oxur::parser::print::Layout;
+use oxur::parser::{optimize, print, query, resolve, sexp};
+use oxur::spec;
+
+enum Error {
+    IncorrectUsage,
+    ReadFile(io::Error),
+    ParseFile {
+        error: Box<OxurError>,
+        filepath: PathBuf,
+        source_code: String,
+    },
+    ParsePattern {
+        error: Box<OxurError>,
+        pattern: String,
+    },
+    Reformat(Box<OxurError>),
+    Eval(Box<OxurError>),
+    ResolveFailed(usize),
+    UnknownLint(String),
+    LintFailed(usize),
+    SpecFailed(usize),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Error::*;
+
+        match self {
+            IncorrectUsage => write!(
+                f,
+                "Usage: oxur [--no-color] <command> [<args>]\n\n\
+                 Commands:\n    \
+                 ast [--format json|sexp] path/to/filename.oxr\n    \
+                 eval path/to/filename.oxr\n    \
+                 fmt [--compact|--expand] path/to/filename.oxr\n    \
+                 lint [--allow|--warn|--deny <lint>]... path/to/filename.oxr\n    \
+                 optimize [--dump] path/to/filename.oxr\n    \
+                 query '<pattern>' path/to/filename.oxr\n    \
+                 spec extract [--eval] [path/to/docs]"
+            ),
+            ReadFile(error) => write!(f, "Unable to read file: {}", error),
+            ParseFile {
+                error,
+                filepath,
+                source_code,
+            } => write!(
+                f,
+                "{}",
+                error.render(&filepath.display().to_string(), source_code)
+            ),
+            ParsePattern { error, pattern } => write!(f, "{}", error.render("<pattern>", pattern)),
+            Reformat(error) => write!(f, "Formatted output does not re-parse: {}", error),
+            // evaluation errors have no span, so this is the header and notes only
+            Eval(error) => write!(f, "{}", error.render("", "").trim_end()),
+            ResolveFailed(count) => {
+                write!(f, "Aborting due to {} name resolution error(s)", count)
+            }
+            UnknownLint(id) => write!(f, "Unknown lint: {}", id),
+            LintFailed(count) => write!(f, "Aborting due to {} denied lint(s)", count),
+            SpecFailed(count) => {
+                write!(f, "Aborting due to {} failing documented example(s)", count)
+            }
+        }
+    }
+}
+
 fn main() {
-    println!("Hello, Oxur!");
+    if let Err(error) = try_main() {
+        let _ = writeln!(io::stderr(), "{}", error);
+        process::exit(1);
+    }
+}
+
+fn try_main() -> Result<(), Error> {
+    let mut args = env::args_os().peekable();
+    let _ = args.next(); // executable name
+
+    // Colors are also disabled when NO_COLOR is set, which `colored` checks
+    // on its own.
+    if matches!(args.peek(), Some(arg) if arg == "--no-color") {
+        let _ = args.next();
+        colored::control::set_override(false);
+    }
+
+    match args.next() {
+        Some(ref command) if command == "ast" => ast_command(args.collect()),
+        Some(ref command) if command == "eval" => eval_command(args.collect()),
+        Some(ref command) if command == "fmt" => fmt_command(args.collect()),
+        Some(ref command) if command == "lint" => lint_command(args.collect()),
+        Some(ref command) if command == "optimize" => optimize_command(args.collect()),
+        Some(ref command) if command == "query" => query_command(args.collect()),
+        Some(ref command) if command == "spec" => spec_command(args.collect()),
+        _ => Err(Error::IncorrectUsage),
+    }
+}
+
+fn read_expr(filepath: &PathBuf) -> Result<sexp::Expr, Error> {
+    let code = fs::read_to_string(filepath).map_err(Error::ReadFile)?;
+    sexp::parse_from_str(&code).map_err(|error| Error::ParseFile {
+        error: Box::new(error),
+        filepath: filepath.clone(),
+        source_code: code.clone(),
+    })
+}
+
+// oxur ast [--format json|sexp] <path>
+//
+// Dumps the parsed tree; `sexp` (the default) prints canonical s-expressions
+// laid out by `print::Printer`.
+fn ast_command(args: Vec<OsString>) -> Result<(), Error> {
+    let (format, filepath) = match args.as_slice() {
+        [path] => ("sexp".into(), PathBuf::from(path)),
+        [flag, format, path] if flag == "--format" => {
+            (format.to_string_lossy(), PathBuf::from(path))
+        }
+        _ => return Err(Error::IncorrectUsage),
+    };
+
+    let expr = read_expr(&filepath)?;
+    match format.as_ref() {
+        "json" => println!("{}", print::to_json(&expr)),
+        "sexp" => println!("{}", print::Printer::default().print(&expr)),
+        _ => return Err(Error::IncorrectUsage),
+    }
+
+    Ok(())
+}
+
+// oxur eval <path>
+//
+// Resolves every name in the file, then runs it through the tree-walking
+// interpreter and prints the value. Resolution errors and warnings go to
+// stderr, and any error stops the file from running.
+fn eval_command(args: Vec<OsString>) -> Result<(), Error> {
+    let filepath = match args.as_slice() {
+        [path] => PathBuf::from(path),
+        _ => return Err(Error::IncorrectUsage),
+    };
+
+    let expr = read_expr(&filepath)?;
+    let resolution = resolve::resolve(&expr);
+    for error in &resolution.errors {
+        let _ = writeln!(
+            io::stderr(),
+            "{}: {}",
+            filepath.display(),
+            error.render("", "").trim_end()
+        );
+    }
+    if resolution.has_errors() {
+        let count = resolution
+            .errors
+            .iter()
+            .filter(|error| error.severity == Severity::Error)
+            .count();
+        return Err(Error::ResolveFailed(count));
+    }
+
+    let value = Interpreter::default()
+        .eval(&expr, &Env::default())
+        .map_err(|error| Error::Eval(Box::new(error)))?;
+    println!("{}", value);
+
+    Ok(())
+}
+
+// oxur fmt [--compact|--expand] <path>
+//
+// Prints the reformatted file. The output is re-parsed before printing, so a
+// printer bug can never silently change the program.
+fn fmt_command(args: Vec<OsString>) -> Result<(), Error> {
+    let (layout, filepath) = match args.as_slice() {
+        [path] => (Layout::Fit, PathBuf::from(path)),
+        [flag, path] if flag == "--compact" => (Layout::Compact, PathBuf::from(path)),
+        [flag, path] if flag == "--expand" => (Layout::Expanded, PathBuf::from(path)),
+        _ => return Err(Error::IncorrectUsage),
+    };
+
+    let expr = read_expr(&filepath)?;
+    let printer = print::Printer {
+        layout,
+        ..Default::default()
+    };
+    let formatted = printer.print(&expr);
+    match sexp::parse_from_str(&formatted) {
+        Ok(ref reparsed) if *reparsed == expr => {}
+        Ok(_) => {
+            return Err(Error::Reformat(Box::new(OxurError::new(
+                ErrorCode::Syntax,
+                "the re-parsed tree differs from the original",
+            ))))
+        }
+        Err(error) => return Err(Error::Reformat(Box::new(error))),
+    }
+    println!("{}", formatted);
+
+    Ok(())
+}
+
+// oxur lint [--allow|--warn|--deny <lint>]... <path>
+//
+// Prints each report with the level of the lint that made it, and fails if
+// any denied lint fires.
+fn lint_command(args: Vec<OsString>) -> Result<(), Error> {
+    let mut linter = Linter::default();
+    let mut args = args.into_iter();
+    let mut filepath = None;
+    while let Some(arg) = args.next() {
+        let level = match arg.to_str() {
+            Some("--allow") => Level::Allow,
+            Some("--warn") => Level::Warn,
+            Some("--deny") => Level::Deny,
+            _ if filepath.is_none() => {
+                filepath = Some(PathBuf::from(arg));
+                continue;
+            }
+            _ => return Err(Error::IncorrectUsage),
+        };
+        let id = args.next().ok_or(Error::IncorrectUsage)?;
+        let id = id.to_string_lossy();
+        if !linter.set_level(&id, level) {
+            return Err(Error::UnknownLint(id.into_owned()));
+        }
+    }
+    let filepath = filepath.ok_or(Error::IncorrectUsage)?;
+
+    let expr = read_expr(&filepath)?;
+    let reports = linter.check(&expr);
+    for report in &reports {
+        println!(
+            "{}: {}",
+            filepath.display(),
+            report.render("", "").trim_end()
+        );
+    }
+
+    let denied = reports
+        .iter()
+        .filter(|report| report.severity == Severity::Error)
+        .count();
+    if denied > 0 {
+        return Err(Error::LintFailed(denied));
+    }
+
+    Ok(())
+}
+
+// oxur optimize [--dump] <path>
+//
+// Without `--dump` the optimized expression is printed as source, laid out
+// like `oxur fmt`; with it, the tree is dumped as JSON, as by
+// `oxur ast --format json`.
+fn optimize_command(args: Vec<OsString>) -> Result<(), Error> {
+    let (dump, filepath) = match args.as_slice() {
+        [path] => (false, PathBuf::from(path)),
+        [flag, path] if flag == "--dump" => (true, PathBuf::from(path)),
+        _ => return Err(Error::IncorrectUsage),
+    };
+
+    let optimized = optimize::optimize(read_expr(&filepath)?);
+    if dump {
+        println!("{}", print::to_json(&optimized));
+    } else {
+        println!("{}", print::Printer::default().print(&optimized));
+    }
+
+    Ok(())
+}
+
+// oxur query '<pattern>' <path>
+//
+// Prints each matching form with its location, followed by any captures.
+fn query_command(args: Vec<OsString>) -> Result<(), Error> {
+    let (pattern, filepath) = match args.as_slice() {
+        [pattern, path] => (pattern.to_string_lossy(), PathBuf::from(path)),
+        _ => return Err(Error::IncorrectUsage),
+    };
+
+    let pattern = query::pattern_from_str(&pattern).map_err(|error| Error::ParsePattern {
+        error: Box::new(error),
+        pattern: pattern.to_string(),
+    })?;
+    let expr = read_expr(&filepath)?;
+    for found in query::query(&pattern, &expr) {
+        println!("{}:{} {}", filepath.display(), found.location(), found.expr);
+        for (name, capture) in &found.captures {
+            println!("    ?{} = {}", name, capture);
+        }
+    }
+
+    Ok(())
+}
+
+// oxur spec extract [--eval] [<path>]
+//
+// Checks the ```oxur code blocks of every Markdown file under `path` (`docs`
+// by default): each block must parse and, with `--eval`, every form in it must
+// evaluate. Failures are reported against the document they came from.
+fn spec_command(args: Vec<OsString>) -> Result<(), Error> {
+    let (eval, path) = match args.as_slice() {
+        [command] if command == "extract" => (false, PathBuf::from("docs")),
+        [command, flag] if command == "extract" && flag == "--eval" => {
+            (true, PathBuf::from("docs"))
+        }
+        [command, path] if command == "extract" => (false, PathBuf::from(path)),
+        [command, flag, path] if command == "extract" && flag == "--eval" => {
+            (true, PathBuf::from(path))
+        }
+        _ => return Err(Error::IncorrectUsage),
+    };
+
+    let docs = spec::find_markdown(&path).map_err(Error::ReadFile)?;
+
+    let (mut total, mut failed) = (0, 0);
+    for doc in &docs {
+        let text = fs::read_to_string(doc).map_err(Error::ReadFile)?;
+        for block in spec::oxur_blocks(&text) {
+            total += 1;
+            let checked = Parser::default().parse_all(block.code).and_then(|exprs| {
+                if eval {
+                    for expr in &exprs {
+                        Interpreter::default().eval(expr, &Env::default())?;
+                    }
+                }
+                Ok(())
+            });
+            if let Err(error) = checked {
+                failed += 1;
+                let error = block.locate(error);
+                let _ = writeln!(
+                    io::stderr(),
+                    "{}",
+                    error.render(&doc.display().to_string(), &text)
+                );
+            }
+        }
+    }
+    println!(
+        "{} of {} documented example(s) passed",
+        total - failed,
+        total
+    );
+
+    if failed > 0 {
+        return Err(Error::SpecFailed(failed));
+    }
+
+    Ok(())
 }
@@ -0,0 +1,116 @@
+//! Syntax tree traversal to transform the nodes of an owned `Expr` tree.
+//!
+//! Each method of the [`Fold`] trait is a hook that can be overridden to
+//! customize the behavior when transforming the corresponding type of node.
+//! By default, every method recursively visits the substructure of the input
+//! by invoking the right folder method of each of its fields, rebuilding the
+//! node unchanged.
+//!
+//! As with `visit`, the trait methods delegate to free functions of the same
+//! name so that overrides can reuse the default traversal.
+
+use alloc::boxed::Box;
+
+use super::sexp::{Atom, BuiltIn, Expr};
+
+pub trait Fold {
+    fn fold_expr(&mut self, node: Expr) -> Expr {
+        fold_expr(self, node)
+    }
+
+    fn fold_atom(&mut self, node: Atom) -> Atom {
+        fold_atom(self, node)
+    }
+
+    fn fold_builtin(&mut self, node: BuiltIn) -> BuiltIn {
+        fold_builtin(self, node)
+    }
+}
+
+pub fn fold_expr<F>(f: &mut F, node: Expr) -> Expr
+where
+    F: Fold + ?Sized,
+{
+    match node {
+        Expr::Constant(atom) => Expr::Constant(f.fold_atom(atom)),
+        Expr::Application(head, tail) => Expr::Application(
+            Box::new(f.fold_expr(*head)),
+            tail.into_iter().map(|expr| f.fold_expr(expr)).collect(),
+        ),
+        Expr::If(pred, true_branch) => Expr::If(
+            Box::new(f.fold_expr(*pred)),
+            Box::new(f.fold_expr(*true_branch)),
+        ),
+        Expr::IfElse(pred, true_branch, false_branch) => Expr::IfElse(
+            Box::new(f.fold_expr(*pred)),
+            Box::new(f.fold_expr(*true_branch)),
+            Box::new(f.fold_expr(*false_branch)),
+        ),
+        Expr::Quote(exprs) => {
+            Expr::Quote(exprs.into_iter().map(|expr| f.fold_expr(expr)).collect())
+        }
+        Expr::Let(bindings, body) => Expr::Let(
+            bindings
+                .into_iter()
+                .map(|(name, value)| (name, f.fold_expr(value)))
+                .collect(),
+            Box::new(f.fold_expr(*body)),
+        ),
+        Expr::Lambda(params, body) => Expr::Lambda(params, Box::new(f.fold_expr(*body))),
+    }
+}
+
+pub fn fold_atom<F>(f: &mut F, node: Atom) -> Atom
+where
+    F: Fold + ?Sized,
+{
+    match node {
+        Atom::BuiltIn(builtin) => Atom::BuiltIn(f.fold_builtin(builtin)),
+        other => other,
+    }
+}
+
+pub fn fold_builtin<F>(_f: &mut F, node: BuiltIn) -> BuiltIn
+where
+    F: Fold + ?Sized,
+{
+    node
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::sexp::parse_from_str;
+
+    /// Adds one to every number.
+    struct Increment;
+
+    impl Fold for Increment {
+        fn fold_atom(&mut self, node: Atom) -> Atom {
+            match node {
+                Atom::Num(n) => Atom::Num(n + 1),
+                other => fold_atom(self, other),
+            }
+        }
+    }
+
+    fn increment(src: &str) -> Expr {
+        Increment.fold_expr(parse_from_str(src).unwrap())
+    }
+
+    #[test]
+    fn folds_through_let_and_fn() {
+        assert_eq!(
+            increment("(let ((x 1) (f (fn (y) (+ x y 2)))) (f 3))"),
+            parse_from_str("(let ((x 2) (f (fn (y) (+ x y 3)))) (f 4))").unwrap()
+        );
+    }
+
+    #[test]
+    fn folds_every_form() {
+        assert_eq!(
+            increment("(if (= 1 2) '(3 :a) (if #t 4))"),
+            parse_from_str("(if (= 2 3) '(4 :a) (if #t 5))").unwrap()
+        );
+    }
+}
@@ -0,0 +1,347 @@
+//! A tree-walking interpreter for the core language: the built-in operators,
+//! `if`, `let`, and `fn` with closures.
+//!
+//! Unlike `sexp::eval_expression`, which only reduces closed expressions, the
+//! interpreter evaluates in an environment of bound names, and when it fails
+//! it says why. Calls are limited in depth, so a runaway recursion such as
+//! `((fn (f) (f f)) (fn (f) (f f)))` is an error rather than a stack overflow.
+
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+use super::sexp::{apply_builtin, Atom, Expr};
+use crate::error::{ErrorCode, OxurError, Result};
+
+/// The result of evaluating an expression.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Atom(Atom),
+    Quote(Vec<Expr>),
+    Closure(Rc<Closure>),
+}
+
+impl PartialEq for Value {
+    /// Closures are only equal to themselves.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Atom(a), Value::Atom(b)) => a == b,
+            (Value::Quote(a), Value::Quote(b)) => a == b,
+            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    /// The value written as an expression; closures have no literal form.
+    fn to_expr(&self) -> Option<Expr> {
+        match self {
+            Value::Atom(atom) => Some(Expr::Constant(atom.clone())),
+            Value::Quote(exprs) => Some(Expr::Quote(exprs.clone())),
+            Value::Closure(_) => None,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Closure(closure) => write!(f, "#<fn ({})>", closure.params.join(" ")),
+            other => write!(f, "{}", other.to_expr().unwrap()),
+        }
+    }
+}
+
+/// A function together with the environment it was created in.
+pub struct Closure {
+    pub params: Vec<String>,
+    pub body: Expr,
+    env: Env,
+}
+
+impl fmt::Debug for Closure {
+    // The captured environment is left out: it holds every enclosing binding.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Closure")
+            .field("params", &self.params)
+            .field("body", &self.body)
+            .finish()
+    }
+}
+
+/// Bound names, innermost first. Binding a name shares the rest of the chain,
+/// so capturing an environment in a closure is cheap.
+#[derive(Clone, Default)]
+pub struct Env(Option<Rc<Binding>>);
+
+struct Binding {
+    name: String,
+    value: Value,
+    parent: Env,
+}
+
+impl Env {
+    pub fn bind(&self, name: impl Into<String>, value: Value) -> Env {
+        Env(Some(Rc::new(Binding {
+            name: name.into(),
+            value,
+            parent: self.clone(),
+        })))
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Value> {
+        let mut env = self;
+        while let Some(binding) = &env.0 {
+            if binding.name == name {
+                return Some(&binding.value);
+            }
+            env = &binding.parent;
+        }
+        None
+    }
+}
+
+impl Drop for Env {
+    // Unlink the chain iteratively; the default recursive drop can overflow
+    // the stack on a long run of `let` bindings.
+    fn drop(&mut self) {
+        let mut next = self.0.take();
+        while let Some(binding) = next {
+            next = match Rc::try_unwrap(binding) {
+                Ok(mut binding) => binding.parent.0.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+fn eval_error(message: impl Into<String>) -> OxurError {
+    OxurError::new(ErrorCode::Eval, message)
+}
+
+#[derive(Debug, Clone)]
+pub struct Interpreter {
+    /// How deeply evaluation may nest, counting both sub-expressions and
+    /// function calls.
+    pub max_depth: usize,
+}
+
+impl Default for Interpreter {
+    /// The default depth leaves room for calls beyond the deepest expression
+    /// `limits::Limits` lets through, while staying within a 2 MiB thread
+    /// stack in an unoptimized build.
+    fn default() -> Self {
+        Interpreter { max_depth: 400 }
+    }
+}
+
+impl Interpreter {
+    pub fn eval(&self, expr: &Expr, env: &Env) -> Result<Value> {
+        self.eval_at(expr, env, 0)
+    }
+
+    fn eval_at(&self, expr: &Expr, env: &Env, depth: usize) -> Result<Value> {
+        if depth >= self.max_depth {
+            return Err(eval_error(format!(
+                "evaluation nested more than {} deep",
+                self.max_depth
+            ))
+            .with_note("is a function calling itself without end?"));
+        }
+        let depth = depth + 1;
+
+        match expr {
+            Expr::Constant(Atom::Symbol(name)) => env
+                .lookup(name)
+                .cloned()
+                .ok_or_else(|| eval_error(format!("unbound symbol `{}`", name))),
+            Expr::Constant(atom) => Ok(Value::Atom(atom.clone())),
+            Expr::Quote(exprs) => Ok(Value::Quote(exprs.clone())),
+            Expr::If(pred, true_branch) => {
+                if self.eval_predicate(pred, env, depth)? {
+                    self.eval_at(true_branch, env, depth)
+                } else {
+                    Err(eval_error(
+                        "`if` without an else branch has no value when its predicate is false",
+                    ))
+                }
+            }
+            Expr::IfElse(pred, true_branch, false_branch) => {
+                if self.eval_predicate(pred, env, depth)? {
+                    self.eval_at(true_branch, env, depth)
+                } else {
+                    self.eval_at(false_branch, env, depth)
+                }
+            }
+            Expr::Let(bindings, body) => self.eval_let(bindings, body, env, depth),
+            Expr::Lambda(params, body) => Ok(Value::Closure(Rc::new(Closure {
+                params: params.clone(),
+                body: (**body).clone(),
+                env: env.clone(),
+            }))),
+            Expr::Application(head, tail) => self.eval_application(head, tail, env, depth),
+        }
+    }
+
+    fn eval_let(
+        &self,
+        bindings: &[(String, Expr)],
+        body: &Expr,
+        env: &Env,
+        depth: usize,
+    ) -> Result<Value> {
+        let mut env = env.clone();
+        for (name, value) in bindings {
+            let value = self.eval_at(value, &env, depth)?;
+            env = env.bind(name.as_str(), value);
+        }
+        self.eval_at(body, &env, depth)
+    }
+
+    fn eval_application(
+        &self,
+        head: &Expr,
+        tail: &[Expr],
+        env: &Env,
+        depth: usize,
+    ) -> Result<Value> {
+        let head = self.eval_at(head, env, depth)?;
+        let mut args = Vec::with_capacity(tail.len());
+        for arg in tail {
+            args.push(self.eval_at(arg, env, depth)?);
+        }
+        self.apply(head, args, depth)
+    }
+
+    fn eval_predicate(&self, pred: &Expr, env: &Env, depth: usize) -> Result<bool> {
+        match self.eval_at(pred, env, depth)? {
+            Value::Atom(Atom::Boolean(b)) => Ok(b),
+            other => Err(eval_error(format!(
+                "`if` predicate must be a boolean, found `{}`",
+                other
+            ))),
+        }
+    }
+
+    fn apply(&self, head: Value, args: Vec<Value>, depth: usize) -> Result<Value> {
+        match head {
+            Value::Atom(Atom::BuiltIn(bi)) => {
+                let args = args
+                    .iter()
+                    .map(Value::to_expr)
+                    .collect::<Option<Vec<Expr>>>()
+                    .ok_or_else(|| {
+                        eval_error(format!("`{}` can't be applied to a function", bi))
+                    })?;
+                apply_builtin(bi, args)
+                    .map(Value::Atom)
+                    .ok_or_else(|| eval_error(format!("invalid arguments to `{}`", bi)))
+            }
+            Value::Closure(closure) => {
+                if closure.params.len() != args.len() {
+                    return Err(eval_error(format!(
+                        "function takes {} argument(s) but {} were supplied",
+                        closure.params.len(),
+                        args.len()
+                    )));
+                }
+                let env = closure
+                    .params
+                    .iter()
+                    .zip(args)
+                    .fold(closure.env.clone(), |env, (name, value)| {
+                        env.bind(name.as_str(), value)
+                    });
+                self.eval_at(&closure.body, &env, depth)
+            }
+            other => Err(eval_error(format!("`{}` is not a function", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::limits::Limits;
+    use crate::parser::sexp::parse_from_str;
+
+    fn eval(src: &str) -> Result<Value> {
+        Interpreter::default().eval(&parse_from_str(src)?, &Env::default())
+    }
+
+    fn num(n: i32) -> Value {
+        Value::Atom(Atom::Num(n))
+    }
+
+    #[test]
+    fn let_binds_sequentially() {
+        assert_eq!(eval("(let ((x 2) (y (+ x 1))) (* x y))"), Ok(num(6)));
+        assert_eq!(eval("(let ((x 1) (x (+ x 1))) x)"), Ok(num(2)));
+        assert_eq!(eval("(let () 7)"), Ok(num(7)));
+    }
+
+    #[test]
+    fn closures_capture_their_environment() {
+        let src = "(let ((n 10) (add-n (fn (x) (+ x n))) (n 20)) (add-n 1))";
+        assert_eq!(eval(src), Ok(num(11)));
+        let src = "(let ((make-adder (fn (n) (fn (x) (+ x n))))) ((make-adder 3) 4))";
+        assert_eq!(eval(src), Ok(num(7)));
+    }
+
+    #[test]
+    fn comparisons_chain() {
+        assert_eq!(eval("(< 1 2 3)"), Ok(Value::Atom(Atom::Boolean(true))));
+        assert_eq!(eval("(>= 3 3 4)"), Ok(Value::Atom(Atom::Boolean(false))));
+    }
+
+    #[test]
+    fn errors_say_what_went_wrong() {
+        let cases = [
+            (
+                "((fn (x y) x) 1)",
+                "function takes 2 argument(s) but 1 were supplied",
+            ),
+            ("(x 1)", "unbound symbol `x`"),
+            ("(1 2)", "`1` is not a function"),
+            ("(+ (fn (x) x) 1)", "`+` can't be applied to a function"),
+            ("(if 1 2 3)", "`if` predicate must be a boolean, found `1`"),
+        ];
+        for &(src, message) in &cases {
+            let error = eval(src).unwrap_err();
+            assert_eq!(error.code, ErrorCode::Eval, "{}", src);
+            assert_eq!(error.message, message, "{}", src);
+        }
+    }
+
+    #[test]
+    fn runaway_recursion_hits_the_depth_limit() {
+        let error = eval("((fn (f) (f f)) (fn (f) (f f)))").unwrap_err();
+        assert_eq!(error.message, "evaluation nested more than 400 deep");
+
+        let shallow = Interpreter { max_depth: 3 };
+        let expr = parse_from_str("(+ 1 (+ 2 (+ 3 4)))").unwrap();
+        assert!(shallow.eval(&expr, &Env::default()).is_err());
+        let expr = parse_from_str("(+ 1 2)").unwrap();
+        assert_eq!(shallow.eval(&expr, &Env::default()), Ok(num(3)));
+    }
+
+    #[test]
+    fn the_deepest_parseable_expression_evaluates() {
+        let depth = Limits::default().max_depth;
+        let src = format!("{}1{}", "(+ 1 ".repeat(depth), ")".repeat(depth));
+        assert_eq!(eval(&src), Ok(num(depth as i32 + 1)));
+    }
+
+    #[test]
+    fn long_environments_drop_without_overflowing() {
+        let mut env = Env::default();
+        for i in 0..1_000_000 {
+            env = env.bind("x", num(i));
+        }
+        assert_eq!(env.lookup("x"), Some(&num(999_999)));
+        drop(env);
+    }
+}
@@ -0,0 +1,140 @@
+//! Guard rails for parsing untrusted input.
+//!
+//! The combinators in `sexp` recurse once per level of nesting (as do the
+//! evaluator, the folder and the printer), so adversarial input like a few
+//! thousand open parens would overflow the stack. `Parser` measures nesting
+//! with an explicit stack before any recursive parsing happens, and rejects
+//! input that is too deep or too large with a structured error instead.
+//!
+//! The combinators themselves still recurse: with the depth bounded up front
+//! that recursion is safe, and it keeps nom's error contexts, which an
+//! explicit-stack parser would have to rebuild by hand.
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use super::sexp::{parse_expr, Expr};
+use crate::error::{ErrorCode, OxurError, Result};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Limits {
+    /// The maximum number of nested lists (including quoted lists).
+    pub max_depth: usize,
+    /// The maximum length of the input, in bytes.
+    pub max_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: 256,
+            max_size: 1 << 20,
+        }
+    }
+}
+
+impl Limits {
+    /// Check `src` against these limits without parsing it.
+    pub fn check(&self, src: &str) -> Result<()> {
+        if src.len() > self.max_size {
+            // the span has to start on a char boundary to be rendered
+            let mut start = self.max_size;
+            while !src.is_char_boundary(start) {
+                start -= 1;
+            }
+            return Err(OxurError::new(
+                ErrorCode::LimitExceeded,
+                format!("input is larger than {} bytes", self.max_size),
+            )
+            .with_span(start, src.len()));
+        }
+
+        // Only the offsets of the currently open parens are kept; that is
+        // all that is needed to point at the one that went too deep.
+        let mut open = Vec::new();
+        for (offset, c) in src.char_indices() {
+            match c {
+                '(' => {
+                    open.push(offset);
+                    if open.len() > self.max_depth {
+                        return Err(OxurError::new(
+                            ErrorCode::LimitExceeded,
+                            format!("expression nested more than {} deep", self.max_depth),
+                        )
+                        .with_span(offset, offset + 1)
+                        .with_note(format!("outermost list opened at offset {}", open[0])));
+                    }
+                }
+                ')' => {
+                    open.pop();
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses complete expressions within a set of `Limits`.
+#[derive(Debug, Default, Clone)]
+pub struct Parser {
+    pub limits: Limits,
+}
+
+impl Parser {
+    pub fn new(limits: Limits) -> Self {
+        Parser { limits }
+    }
+
+    /// Parse a complete source string holding a single expression.
+    pub fn parse(&self, src: &str) -> Result<Expr> {
+        self.limits.check(src)?;
+        match parse_expr(src) {
+            Ok((rest, exp)) if rest.trim().is_empty() => Ok(exp),
+            Ok((rest, _)) => {
+                let start = src.len() - rest.trim_start().len();
+                Err(OxurError::new(
+                    ErrorCode::TrailingInput,
+                    "unexpected input after expression",
+                )
+                .with_span(start, src.trim_end().len()))
+            }
+            Err(e) => Err(OxurError::from_nom(src, e)),
+        }
+    }
+
+    /// Parse a source string holding any number of expressions, such as a
+    /// documentation example.
+    pub fn parse_all(&self, src: &str) -> Result<Vec<Expr>> {
+        self.limits.check(src)?;
+        let mut exprs = Vec::new();
+        let mut rest = src;
+        while !rest.trim().is_empty() {
+            let (remaining, exp) = parse_expr(rest).map_err(|e| OxurError::from_nom(src, e))?;
+            exprs.push(exp);
+            rest = remaining;
+        }
+        Ok(exprs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    #[test]
+    fn size_error_span_starts_on_a_char_boundary() {
+        // the odd-length prefix puts `max_size` in the middle of an `é`
+        let mut src = String::from("x");
+        src.push_str(&"é".repeat(600_000));
+        assert!(!src.is_char_boundary(Limits::default().max_size));
+        let error = Limits::default().check(&src).unwrap_err();
+        assert_eq!(error.code, ErrorCode::LimitExceeded);
+        let span = error.span.unwrap();
+        assert!(src.is_char_boundary(span.start));
+        assert!(span.start <= Limits::default().max_size);
+        #[cfg(feature = "std")]
+        error.render("big.oxr", &src);
+    }
+}
@@ -0,0 +1,387 @@
+//! Style checks over parsed expressions.
+//!
+//! Each `Rule` declares a `Lint` with a stable ID and a default level, and
+//! reports an `OxurError` with code `E0200` for each form it objects to. The
+//! level of any lint can be overridden per run (`allow`, `warn`, `deny`); it
+//! decides the severity of the reports, and whether they are made at all.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::query::Path;
+use super::resolve;
+use super::sexp::Expr;
+use super::visit::{self, Visit};
+use crate::error::{ErrorCode, OxurError, Severity};
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Lint {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub default_level: Level,
+}
+
+/// A form a rule objected to: where it is, in the same form as
+/// `query::Match::path`, and what is wrong with it.
+pub type Report = (Vec<usize>, OxurError);
+
+pub trait Rule {
+    fn lint(&self) -> &'static Lint;
+
+    /// Report every offending form in `expr`, appending to `out`. The severity
+    /// is filled in by the `Linter`, from the level of the lint.
+    fn check(&self, expr: &Expr, out: &mut Vec<Report>);
+}
+
+pub static DEEP_NESTING: Lint = Lint {
+    id: "deep-nesting",
+    description: "forms nested deeper than the configured maximum",
+    default_level: Level::Warn,
+};
+
+/// Flags forms nested more than `max_depth` lists deep. Only the outermost
+/// form past the limit is reported, rather than each of its descendants.
+pub struct DeepNesting {
+    pub max_depth: usize,
+}
+
+impl Default for DeepNesting {
+    fn default() -> Self {
+        DeepNesting { max_depth: 8 }
+    }
+}
+
+struct NestingVisitor<'r> {
+    max_depth: usize,
+    /// The number of lists enclosing the node being visited.
+    depth: usize,
+    path: Vec<usize>,
+    out: &'r mut Vec<Report>,
+}
+
+impl<'ast, 'r> Visit<'ast> for NestingVisitor<'r> {
+    fn visit_expr(&mut self, node: &'ast Expr) {
+        if let Expr::Constant(_) = node {
+            return;
+        }
+        if self.depth == self.max_depth {
+            let message = format!(
+                "form is nested more than {} deep at {}",
+                self.max_depth,
+                Path(&self.path)
+            );
+            let report = (self.path.clone(), OxurError::new(ErrorCode::Lint, message));
+            self.out.push(report);
+            return;
+        }
+        self.depth += 1;
+        visit::visit_expr(self, node);
+        self.depth -= 1;
+    }
+
+    fn enter_child(&mut self, steps: &[usize]) {
+        self.path.extend_from_slice(steps);
+    }
+
+    fn leave_child(&mut self, steps: &[usize]) {
+        self.path.truncate(self.path.len() - steps.len());
+    }
+}
+
+impl Rule for DeepNesting {
+    fn lint(&self) -> &'static Lint {
+        &DEEP_NESTING
+    }
+
+    fn check(&self, expr: &Expr, out: &mut Vec<Report>) {
+        NestingVisitor {
+            max_depth: self.max_depth,
+            depth: 0,
+            path: Vec::new(),
+            out,
+        }
+        .visit_expr(expr);
+    }
+}
+
+pub static UNUSED_BINDING: Lint = Lint {
+    id: "unused-binding",
+    description: "names bound by let or fn that nothing refers to",
+    default_level: Level::Warn,
+};
+
+/// Flags `let` bindings and `fn` parameters that no symbol in their scope
+/// refers to.
+#[derive(Default)]
+pub struct UnusedBinding;
+
+impl Rule for UnusedBinding {
+    fn lint(&self) -> &'static Lint {
+        &UNUSED_BINDING
+    }
+
+    fn check(&self, expr: &Expr, out: &mut Vec<Report>) {
+        for binding in resolve::resolve(expr).bindings {
+            if binding.uses == 0 {
+                let message = format!(
+                    "`{}` is bound but never used at {}",
+                    binding.name,
+                    Path(&binding.path)
+                );
+                out.push((binding.path, OxurError::new(ErrorCode::Lint, message)));
+            }
+        }
+    }
+}
+
+pub static CAMEL_CASE_NAME: Lint = Lint {
+    id: "camel-case-name",
+    description: "names written in camelCase instead of kebab-case",
+    default_level: Level::Warn,
+};
+
+/// Flags names with an uppercase letter. Only bindings are checked: every
+/// other symbol refers to one, so the binding is the place to rename it.
+#[derive(Default)]
+pub struct CamelCaseName;
+
+/// `fooBar` as `foo-bar`.
+fn kebab_case(name: &str) -> String {
+    let mut out = String::new();
+    let mut after_word = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if after_word {
+                out.push('-');
+            }
+            out.push(c.to_ascii_lowercase());
+            after_word = false;
+        } else {
+            out.push(c);
+            after_word = c.is_ascii_alphanumeric();
+        }
+    }
+    out
+}
+
+impl Rule for CamelCaseName {
+    fn lint(&self) -> &'static Lint {
+        &CAMEL_CASE_NAME
+    }
+
+    fn check(&self, expr: &Expr, out: &mut Vec<Report>) {
+        for binding in resolve::resolve(expr).bindings {
+            if binding.name.chars().any(|c| c.is_ascii_uppercase()) {
+                let message = format!(
+                    "`{}` should be written in kebab-case, as `{}`, at {}",
+                    binding.name,
+                    kebab_case(binding.name),
+                    Path(&binding.path)
+                );
+                out.push((binding.path, OxurError::new(ErrorCode::Lint, message)));
+            }
+        }
+    }
+}
+
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+    levels: BTreeMap<&'static str, Level>,
+}
+
+impl Default for Linter {
+    /// A linter with every built-in rule at its default level.
+    fn default() -> Self {
+        Linter::new()
+            .with_rule(DeepNesting::default())
+            .with_rule(UnusedBinding)
+            .with_rule(CamelCaseName)
+    }
+}
+
+impl Linter {
+    /// A linter with no rules.
+    pub fn new() -> Self {
+        Linter {
+            rules: Vec::new(),
+            levels: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_rule<R: Rule + 'static>(mut self, rule: R) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    pub fn lints(&self) -> impl Iterator<Item = &'static Lint> + '_ {
+        self.rules.iter().map(|rule| rule.lint())
+    }
+
+    /// Override the level of the lint with the given ID, returning `false` if
+    /// no rule declares it.
+    pub fn set_level(&mut self, id: &str, level: Level) -> bool {
+        let lint = self.lints().find(|lint| lint.id == id);
+        match lint {
+            Some(lint) => {
+                self.levels.insert(lint.id, level);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn level(&self, lint: &Lint) -> Level {
+        self.levels
+            .get(lint.id)
+            .copied()
+            .unwrap_or(lint.default_level)
+    }
+
+    /// Run every rule that isn't allowed, returning its reports in source
+    /// order: denied lints as errors, the rest as warnings.
+    pub fn check(&self, expr: &Expr) -> Vec<OxurError> {
+        let mut out = Vec::new();
+        for rule in &self.rules {
+            let lint = rule.lint();
+            let level = self.level(lint);
+            let severity = match level {
+                Level::Allow => continue,
+                Level::Warn => Severity::Warning,
+                Level::Deny => Severity::Error,
+            };
+            let note = format!("`{}` is set to {}", lint.id, level.as_str());
+            let mut reports = Vec::new();
+            rule.check(expr, &mut reports);
+            out.extend(reports.into_iter().map(|(path, error)| {
+                (path, error.with_severity(severity).with_note(note.as_str()))
+            }));
+        }
+        out.sort_by(|a, b| a.0.cmp(&b.0));
+        out.into_iter().map(|(_, error)| error).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::sexp::parse_from_str;
+    use alloc::vec;
+
+    fn check(linter: &Linter, src: &str) -> Vec<OxurError> {
+        linter.check(&parse_from_str(src).unwrap())
+    }
+
+    #[test]
+    fn levels_decide_the_severity() {
+        let src = "(+ 1 (+ 2 (+ 3 4)))";
+        let mut linter = Linter::new().with_rule(DeepNesting { max_depth: 2 });
+        let warnings = check(&linter, src);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, ErrorCode::Lint);
+        assert_eq!(warnings[0].severity, Severity::Warning);
+        assert_eq!(
+            warnings[0].message,
+            "form is nested more than 2 deep at /2/2"
+        );
+        assert_eq!(warnings[0].notes, vec!["`deep-nesting` is set to warn"]);
+
+        assert!(linter.set_level("deep-nesting", Level::Deny));
+        assert_eq!(check(&linter, src)[0].severity, Severity::Error);
+        assert!(linter.set_level("deep-nesting", Level::Allow));
+        assert_eq!(check(&linter, src), Vec::new());
+        assert!(!linter.set_level("no-such-lint", Level::Deny));
+    }
+
+    #[test]
+    fn reports_are_in_source_order() {
+        let linter = Linter::new()
+            .with_rule(DeepNesting { max_depth: 2 })
+            .with_rule(DeepNesting { max_depth: 1 });
+        let messages: Vec<_> = check(&linter, "(+ (+ 1 (+ 2 3)) (+ 4 5))")
+            .into_iter()
+            .map(|error| error.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "form is nested more than 1 deep at /1",
+                "form is nested more than 2 deep at /1/2",
+                "form is nested more than 1 deep at /2",
+            ]
+        );
+    }
+
+    fn messages(linter: &Linter, src: &str) -> Vec<String> {
+        check(linter, src)
+            .into_iter()
+            .map(|error| error.message)
+            .collect()
+    }
+
+    #[test]
+    fn unused_bindings_are_reported() {
+        let linter = Linter::new().with_rule(UnusedBinding);
+        assert_eq!(
+            messages(&linter, "(let ((x 1) (y 2) (f (fn (a b) a))) (f y 3))"),
+            vec![
+                "`x` is bound but never used at /1/0/0",
+                "`b` is bound but never used at /1/2/1/1/1",
+            ]
+        );
+        // a shadowed binding is unused if only the new one is referred to
+        assert_eq!(
+            messages(&linter, "(let ((x 1) (x 2)) x)"),
+            vec!["`x` is bound but never used at /1/0/0"]
+        );
+        assert_eq!(
+            messages(&linter, "(let ((x 1) (x (+ x 1))) x)"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn camel_case_names_are_reported() {
+        let linter = Linter::new().with_rule(CamelCaseName);
+        assert_eq!(
+            messages(
+                &linter,
+                "(let ((addOne (fn (nValue) (+ nValue 1))) (x-y 2)) (addOne x-y))"
+            ),
+            vec![
+                "`addOne` should be written in kebab-case, as `add-one`, at /1/0/0",
+                "`nValue` should be written in kebab-case, as `n-value`, at /1/0/1/1/0",
+            ]
+        );
+        assert_eq!(kebab_case("isEmpty?"), "is-empty?");
+        assert_eq!(kebab_case("already-kebab?"), "already-kebab?");
+    }
+
+    #[test]
+    fn the_default_linter_has_every_rule() {
+        let ids: Vec<&str> = Linter::default().lints().map(|lint| lint.id).collect();
+        assert_eq!(
+            ids,
+            vec!["deep-nesting", "unused-binding", "camel-case-name"]
+        );
+    }
+}
@@ -18,7 +18,7 @@ fn main() {
             *
             /)
         456 123)";
-    log::debug!("expression: {:?}\n", sexp::parse_expr(expression_1));
+    log::debug!("expression: {:?}\n", sexp::parse_from_str(expression_1));
     log::debug!("\"{}\"", expression_1);
     log::debug!("evaled gives us: {:?}", sexp::eval_from_str(expression_1));
     // let expr_2 = r#"(mod thing1
@@ -1 +1,10 @@
+pub mod fold;
+pub mod interp;
+pub mod limits;
+pub mod lint;
+pub mod optimize;
+pub mod print;
+pub mod query;
+pub mod resolve;
 pub mod sexp;
+pub mod visit;
@@ -0,0 +1,158 @@
+//! A simple optimization pass over parsed expressions.
+//!
+//! Constant arithmetic is folded using the same rules as
+//! `sexp::eval_expression`, and `if` forms whose predicate is a literal
+//! boolean are replaced by the branch that would be taken. Quoted forms are
+//! data, so they are left exactly as written.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use super::fold::{self, Fold};
+use super::sexp::{eval_expression, Atom, Expr};
+
+/// Fold constants bottom-up, so that `(+ 1 (* 2 3))` becomes `7` in a single
+/// pass.
+#[derive(Debug, Default)]
+pub struct ConstantFolder {
+    /// The number of nodes that were replaced while folding.
+    pub folded: usize,
+}
+
+impl Fold for ConstantFolder {
+    fn fold_expr(&mut self, node: Expr) -> Expr {
+        if let Expr::Quote(_) = node {
+            return node;
+        }
+        match fold::fold_expr(self, node) {
+            Expr::Application(head, tail) => self.fold_application(*head, tail),
+            Expr::If(pred, true_branch) => match *pred {
+                Expr::Constant(Atom::Boolean(true)) => {
+                    self.folded += 1;
+                    *true_branch
+                }
+                pred => Expr::If(Box::new(pred), true_branch),
+            },
+            Expr::IfElse(pred, true_branch, false_branch) => match *pred {
+                Expr::Constant(Atom::Boolean(b)) => {
+                    self.folded += 1;
+                    if b {
+                        *true_branch
+                    } else {
+                        *false_branch
+                    }
+                }
+                pred => Expr::IfElse(Box::new(pred), true_branch, false_branch),
+            },
+            other => other,
+        }
+    }
+}
+
+impl ConstantFolder {
+    fn fold_application(&mut self, head: Expr, tail: Vec<Expr>) -> Expr {
+        let application = Expr::Application(Box::new(head), tail);
+        if !is_foldable(&application) {
+            return application;
+        }
+        // `eval_expression` gives up (rather than panicking) on ill-typed
+        // arguments, overflow and division by zero, in which case the form is
+        // kept as written.
+        match eval_expression(application.clone()) {
+            Some(constant) => {
+                self.folded += 1;
+                constant
+            }
+            None => application,
+        }
+    }
+}
+
+/// An application can be folded when it calls a built-in on constant
+/// arguments.
+fn is_foldable(application: &Expr) -> bool {
+    match application {
+        Expr::Application(head, tail) => {
+            matches!(**head, Expr::Constant(Atom::BuiltIn(_)))
+                && tail.iter().all(|expr| matches!(expr, Expr::Constant(_)))
+        }
+        _ => false,
+    }
+}
+
+/// Run every optimization pass over an expression.
+pub fn optimize(e: Expr) -> Expr {
+    ConstantFolder::default().fold_expr(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::sexp::parse_from_str;
+
+    /// The optimized form of `src`, and how many nodes were folded.
+    fn fold(src: &str) -> (Expr, usize) {
+        let mut folder = ConstantFolder::default();
+        let expr = folder.fold_expr(parse_from_str(src).unwrap());
+        (expr, folder.folded)
+    }
+
+    fn parse(src: &str) -> Expr {
+        parse_from_str(src).unwrap()
+    }
+
+    #[test]
+    fn arithmetic_folds_bottom_up() {
+        assert_eq!(fold("(+ 1 (* 2 3))"), (parse("7"), 2));
+        assert_eq!(fold("(not (< 1 2))"), (parse("#f"), 2));
+    }
+
+    #[test]
+    fn literal_predicates_pick_a_branch() {
+        assert_eq!(fold("(if #t a b)"), (parse("a"), 1));
+        assert_eq!(fold("(if #f a b)"), (parse("b"), 1));
+        assert_eq!(fold("(if (= 1 1) a)"), (parse("a"), 2));
+        // with no else branch there is nothing to replace a false `if` with
+        assert_eq!(fold("(if #f a)"), (parse("(if #f a)"), 0));
+        assert_eq!(fold("(if x a b)"), (parse("(if x a b)"), 0));
+    }
+
+    #[test]
+    fn quoted_lists_are_left_alone() {
+        assert_eq!(
+            fold("'((+ 1 2) (if #t a b))"),
+            (parse("'((+ 1 2) (if #t a b))"), 0)
+        );
+        assert_eq!(
+            fold("(f '((* 2 3)) (* 2 3))"),
+            (parse("(f '((* 2 3)) 6)"), 1)
+        );
+    }
+
+    #[test]
+    fn errors_are_kept_as_written() {
+        for src in &[
+            "(/ 1 0)",
+            "(+ 2147483647 1)",
+            "(- -2147483648 1)",
+            "(* 65536 65536)",
+            "(/ -2147483648 -1)",
+            "(+ 1 #t)",
+        ] {
+            assert_eq!(fold(src), (parse(src), 0), "{}", src);
+        }
+        assert_eq!(fold("(+ (* 2 3) (/ 1 0))"), (parse("(+ 6 (/ 1 0))"), 1));
+    }
+
+    #[test]
+    fn folds_inside_let_and_fn() {
+        assert_eq!(
+            fold("(let ((x (+ 1 2))) (fn (y) (+ y (* x (- 5 3)))))"),
+            (parse("(let ((x 3)) (fn (y) (+ y (* x 2))))"), 2)
+        );
+        assert_eq!(
+            fold("((fn (x) (if #t x 0)) (* 6 7))"),
+            (parse("((fn (x) x) 42)"), 2)
+        );
+    }
+}
@@ -0,0 +1,619 @@
+//! Deterministic printing of parsed expressions.
+//!
+//! `Display` on `Expr` produces the canonical single-line S-expression, which
+//! re-parses to the same tree. `Printer` lays the same output out over several
+//! lines when it doesn't fit (or always, or never, depending on its `Layout`),
+//! and `to_json` gives a stable JSON rendering for tooling and snapshot
+//! comparisons.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Write};
+
+use super::sexp::{Atom, BuiltIn, Expr};
+
+impl Display for BuiltIn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            BuiltIn::Plus => "+",
+            BuiltIn::Minus => "-",
+            BuiltIn::Times => "*",
+            BuiltIn::Divide => "/",
+            BuiltIn::Equal => "=",
+            BuiltIn::Less => "<",
+            BuiltIn::Greater => ">",
+            BuiltIn::LessEqual => "<=",
+            BuiltIn::GreaterEqual => ">=",
+            BuiltIn::Not => "not",
+        })
+    }
+}
+
+impl Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Atom::Num(n) => write!(f, "{}", n),
+            Atom::Keyword(k) => write!(f, ":{}", k),
+            Atom::Boolean(true) => f.write_str("#t"),
+            Atom::Boolean(false) => f.write_str("#f"),
+            Atom::BuiltIn(b) => write!(f, "{}", b),
+            Atom::Symbol(s) => f.write_str(s),
+        }
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter, elements: &[&dyn Display]) -> fmt::Result {
+    f.write_char('(')?;
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            f.write_char(' ')?;
+        }
+        write!(f, "{}", element)?;
+    }
+    f.write_char(')')
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Constant(atom) => write!(f, "{}", atom),
+            Expr::Application(head, tail) => {
+                let mut elements: Vec<&dyn Display> = vec![head];
+                elements.extend(tail.iter().map(|e| e as &dyn Display));
+                write_list(f, &elements)
+            }
+            Expr::If(pred, true_branch) => write_list(f, &[&"if", pred, true_branch]),
+            Expr::IfElse(pred, true_branch, false_branch) => {
+                write_list(f, &[&"if", pred, true_branch, false_branch])
+            }
+            Expr::Quote(exprs) => {
+                f.write_char('\'')?;
+                let elements: Vec<&dyn Display> = exprs.iter().map(|e| e as &dyn Display).collect();
+                write_list(f, &elements)
+            }
+            Expr::Let(bindings, body) => {
+                f.write_str("(let (")?;
+                for (i, (name, value)) in bindings.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(' ')?;
+                    }
+                    write_list(f, &[name, value])?;
+                }
+                write!(f, ") {})", body)
+            }
+            Expr::Lambda(params, body) => {
+                let params: Vec<&dyn Display> = params.iter().map(|p| p as &dyn Display).collect();
+                f.write_str("(fn ")?;
+                write_list(f, &params)?;
+                write!(f, " {})", body)
+            }
+        }
+    }
+}
+
+fn list_width(elements: &[usize]) -> usize {
+    2 + elements.iter().sum::<usize>() + elements.len().saturating_sub(1)
+}
+
+/// The flat width of every node in a tree, keyed by address.
+type Widths = BTreeMap<*const Expr, usize>;
+
+/// Records how wide `expr` and each of its sub-expressions are when printed
+/// flat, in one pass over the tree, and returns the width of `expr`.
+fn measure(expr: &Expr, widths: &mut Widths) -> usize {
+    let width = match expr {
+        Expr::Constant(atom) => atom.to_string().len(),
+        Expr::Application(head, tail) => {
+            let mut elements = vec![measure(head, widths)];
+            elements.extend(tail.iter().map(|e| measure(e, widths)));
+            list_width(&elements)
+        }
+        Expr::If(pred, true_branch) => {
+            list_width(&[2, measure(pred, widths), measure(true_branch, widths)])
+        }
+        Expr::IfElse(pred, true_branch, false_branch) => list_width(&[
+            2,
+            measure(pred, widths),
+            measure(true_branch, widths),
+            measure(false_branch, widths),
+        ]),
+        Expr::Quote(exprs) => {
+            let elements: Vec<usize> = exprs.iter().map(|e| measure(e, widths)).collect();
+            1 + list_width(&elements)
+        }
+        Expr::Let(bindings, body) => {
+            let bindings: Vec<usize> = bindings
+                .iter()
+                .map(|(name, value)| list_width(&[name.len(), measure(value, widths)]))
+                .collect();
+            list_width(&[3, list_width(&bindings), measure(body, widths)])
+        }
+        Expr::Lambda(params, body) => {
+            let params: Vec<usize> = params.iter().map(String::len).collect();
+            list_width(&[2, list_width(&params), measure(body, widths)])
+        }
+    };
+    widths.insert(expr, width);
+    width
+}
+
+/// How `Printer` chooses between flat and broken forms.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Layout {
+    /// Break a form only when it doesn't fit within the line width.
+    Fit,
+    /// Never break: the canonical single-line form, for machine diffing and
+    /// embedding in docs.
+    Compact,
+    /// Break every list, putting each element on a line of its own.
+    Expanded,
+}
+
+/// Lays expressions out within a target line width.
+///
+/// A form that fits on the rest of the current line is printed flat.
+/// Otherwise its head stays on the opening line and every remaining element
+/// goes on a line of its own, indented two columns past the open paren.
+/// Quoted lists are data rather than calls, so their elements are aligned
+/// under the first one instead, as are the bindings of a `let`. With
+/// `Layout::Fit` the values of those bindings also line up in a column,
+/// unless that would push one of them past the line width.
+///
+/// Whatever the layout, the output re-parses to the same tree.
+#[derive(Debug, Clone)]
+pub struct Printer {
+    pub width: usize,
+    pub layout: Layout,
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Printer {
+            width: 80,
+            layout: Layout::Fit,
+        }
+    }
+}
+
+impl Printer {
+    pub fn print(&self, expr: &Expr) -> String {
+        // Fit mode measures every node up front rather than rendering each
+        // one again for every form it is nested in.
+        let mut widths = Widths::new();
+        if self.layout == Layout::Fit {
+            measure(expr, &mut widths);
+        }
+        let mut out = String::new();
+        self.print_expr(&mut out, &widths, expr, 0);
+        out
+    }
+
+    fn print_expr(&self, out: &mut String, widths: &Widths, expr: &Expr, column: usize) {
+        let flat = match self.layout {
+            Layout::Compact => true,
+            Layout::Expanded => false,
+            Layout::Fit => column + widths[&(expr as *const Expr)] <= self.width,
+        };
+        if flat {
+            out.push_str(&expr.to_string());
+            return;
+        }
+        match expr {
+            Expr::Constant(atom) => out.push_str(&atom.to_string()),
+            Expr::Application(head, tail) => {
+                out.push('(');
+                self.print_expr(out, widths, head, column + 1);
+                self.print_body(out, widths, tail.iter(), column + 2);
+            }
+            Expr::If(pred, true_branch) => {
+                out.push_str("(if ");
+                self.print_expr(out, widths, pred, column + 4);
+                self.print_body(out, widths, vec![&**true_branch].into_iter(), column + 2);
+            }
+            Expr::IfElse(pred, true_branch, false_branch) => {
+                out.push_str("(if ");
+                self.print_expr(out, widths, pred, column + 4);
+                self.print_body(
+                    out,
+                    widths,
+                    vec![&**true_branch, &**false_branch].into_iter(),
+                    column + 2,
+                );
+            }
+            Expr::Quote(exprs) => {
+                out.push_str("'(");
+                let mut exprs = exprs.iter();
+                if let Some(first) = exprs.next() {
+                    self.print_expr(out, widths, first, column + 2);
+                }
+                self.print_body(out, widths, exprs, column + 2);
+            }
+            Expr::Let(bindings, body) => {
+                // bindings are aligned under the first one, like quoted data,
+                // and in `Fit` their values line up too if they all still fit
+                let longest = bindings.iter().map(|(name, _)| name.len()).max();
+                let value_column = column + longest.unwrap_or(0) + 8;
+                let align = self.layout == Layout::Fit
+                    && bindings.iter().all(|(_, value)| {
+                        value_column + widths[&(value as *const Expr)] <= self.width
+                    });
+                out.push_str("(let (");
+                for (i, (name, value)) in bindings.iter().enumerate() {
+                    if i > 0 {
+                        out.push('\n');
+                        out.push_str(&" ".repeat(column + 6));
+                    }
+                    let _ = write!(out, "({} ", name);
+                    let value_column = if align {
+                        out.push_str(&" ".repeat(value_column - (column + name.len() + 8)));
+                        value_column
+                    } else {
+                        column + name.len() + 8
+                    };
+                    self.print_expr(out, widths, value, value_column);
+                    out.push(')');
+                }
+                out.push(')');
+                self.print_body(out, widths, vec![&**body].into_iter(), column + 2);
+            }
+            Expr::Lambda(params, body) => {
+                let _ = write!(out, "(fn ({})", params.join(" "));
+                self.print_body(out, widths, vec![&**body].into_iter(), column + 2);
+            }
+        }
+    }
+
+    fn print_body<'a, I>(&self, out: &mut String, widths: &Widths, exprs: I, indent: usize)
+    where
+        I: Iterator<Item = &'a Expr>,
+    {
+        for expr in exprs {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent));
+            self.print_expr(out, widths, expr, indent);
+        }
+        out.push(')');
+    }
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_json_atom(out: &mut String, atom: &Atom) {
+    match atom {
+        Atom::Num(n) => {
+            let _ = write!(out, "{{\"num\": {}}}", n);
+        }
+        Atom::Keyword(k) => {
+            out.push_str("{\"keyword\": ");
+            write_json_string(out, k);
+            out.push('}');
+        }
+        Atom::Boolean(b) => {
+            let _ = write!(out, "{{\"boolean\": {}}}", b);
+        }
+        Atom::BuiltIn(b) => {
+            out.push_str("{\"builtin\": ");
+            write_json_string(out, &b.to_string());
+            out.push('}');
+        }
+        Atom::Symbol(s) => {
+            out.push_str("{\"symbol\": ");
+            write_json_string(out, s);
+            out.push('}');
+        }
+    }
+}
+
+fn write_json_array(out: &mut String, exprs: &[&Expr], depth: usize) {
+    if exprs.is_empty() {
+        out.push_str("[]");
+        return;
+    }
+    out.push('[');
+    for (i, expr) in exprs.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        newline(out, depth + 1);
+        write_json_expr(out, expr, depth + 1);
+    }
+    newline(out, depth);
+    out.push(']');
+}
+
+fn write_json_object(out: &mut String, tag: &str, fields: &[(&str, &Expr)], depth: usize) {
+    let _ = write!(out, "{{\"{}\": {{", tag);
+    for (i, (name, expr)) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        newline(out, depth + 1);
+        let _ = write!(out, "\"{}\": ", name);
+        write_json_expr(out, expr, depth + 1);
+    }
+    newline(out, depth);
+    out.push_str("}}");
+}
+
+fn newline(out: &mut String, depth: usize) {
+    out.push('\n');
+    out.push_str(&"  ".repeat(depth));
+}
+
+fn write_json_expr(out: &mut String, expr: &Expr, depth: usize) {
+    match expr {
+        Expr::Constant(atom) => {
+            out.push_str("{\"constant\": ");
+            write_json_atom(out, atom);
+            out.push('}');
+        }
+        Expr::Application(head, tail) => {
+            out.push_str("{\"application\": {");
+            newline(out, depth + 1);
+            out.push_str("\"head\": ");
+            write_json_expr(out, head, depth + 1);
+            out.push(',');
+            newline(out, depth + 1);
+            out.push_str("\"args\": ");
+            write_json_array(out, &tail.iter().collect::<Vec<_>>(), depth + 1);
+            newline(out, depth);
+            out.push_str("}}");
+        }
+        Expr::If(pred, true_branch) => write_json_object(
+            out,
+            "if",
+            &[("predicate", &**pred), ("then", &**true_branch)],
+            depth,
+        ),
+        Expr::IfElse(pred, true_branch, false_branch) => write_json_object(
+            out,
+            "if",
+            &[
+                ("predicate", &**pred),
+                ("then", &**true_branch),
+                ("else", &**false_branch),
+            ],
+            depth,
+        ),
+        Expr::Quote(exprs) => {
+            out.push_str("{\"quote\": ");
+            write_json_array(out, &exprs.iter().collect::<Vec<_>>(), depth);
+            out.push('}');
+        }
+        Expr::Let(bindings, body) => {
+            out.push_str("{\"let\": {");
+            newline(out, depth + 1);
+            out.push_str("\"bindings\": [");
+            for (i, (name, value)) in bindings.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, depth + 2);
+                out.push_str("{\"name\": ");
+                write_json_string(out, name);
+                out.push_str(", \"value\": ");
+                write_json_expr(out, value, depth + 2);
+                out.push('}');
+            }
+            if !bindings.is_empty() {
+                newline(out, depth + 1);
+            }
+            out.push_str("],");
+            newline(out, depth + 1);
+            out.push_str("\"body\": ");
+            write_json_expr(out, body, depth + 1);
+            newline(out, depth);
+            out.push_str("}}");
+        }
+        Expr::Lambda(params, body) => {
+            out.push_str("{\"fn\": {");
+            newline(out, depth + 1);
+            out.push_str("\"params\": [");
+            for (i, param) in params.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_json_string(out, param);
+            }
+            out.push_str("],");
+            newline(out, depth + 1);
+            out.push_str("\"body\": ");
+            write_json_expr(out, body, depth + 1);
+            newline(out, depth);
+            out.push_str("}}");
+        }
+    }
+}
+
+/// Render an expression as pretty-printed JSON with a fixed field order.
+pub fn to_json(expr: &Expr) -> String {
+    let mut out = String::new();
+    write_json_expr(&mut out, expr, 0);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::sexp::parse_from_str;
+    use alloc::format;
+
+    #[test]
+    fn quoted_elements_align_under_the_first() {
+        let expr = parse_from_str("'(alpha beta gamma)").unwrap();
+        let printer = Printer {
+            width: 10,
+            ..Default::default()
+        };
+        assert_eq!(printer.print(&expr), "'(alpha\n  beta\n  gamma)");
+    }
+
+    #[test]
+    fn measured_widths_match_the_flat_form() {
+        let sources = [
+            "-42",
+            "(+ 1 (* 2 3))",
+            "(if #t :yes)",
+            "(if (= 1 2) '() '(:a #t (- 1 1)))",
+            "(let () 7)",
+            "(let ((x 2) (y (+ x 1))) (* x y))",
+            "(fn () 1)",
+            "(fn (x y) (<= x y))",
+        ];
+        for src in &sources {
+            let expr = parse_from_str(src).unwrap();
+            let mut widths = Widths::new();
+            assert_eq!(measure(&expr, &mut widths), src.len(), "{}", src);
+            assert_eq!(expr.to_string(), *src);
+        }
+    }
+
+    #[test]
+    fn json_snapshot() {
+        let src =
+            "(let ((f (fn (x y) (if (< x y) '(:lo #t) '()))) (g (fn () 1))) (if (f 1 2) (g)))";
+        let expected = r#"{"let": {
+  "bindings": [
+    {"name": "f", "value": {"fn": {
+      "params": ["x", "y"],
+      "body": {"if": {
+        "predicate": {"application": {
+          "head": {"constant": {"builtin": "<"}},
+          "args": [
+            {"constant": {"symbol": "x"}},
+            {"constant": {"symbol": "y"}}
+          ]
+        }},
+        "then": {"quote": [
+          {"constant": {"keyword": "lo"}},
+          {"constant": {"boolean": true}}
+        ]},
+        "else": {"quote": []}
+      }}
+    }}},
+    {"name": "g", "value": {"fn": {
+      "params": [],
+      "body": {"constant": {"num": 1}}
+    }}}
+  ],
+  "body": {"if": {
+    "predicate": {"application": {
+      "head": {"constant": {"symbol": "f"}},
+      "args": [
+        {"constant": {"num": 1}},
+        {"constant": {"num": 2}}
+      ]
+    }},
+    "then": {"application": {
+      "head": {"constant": {"symbol": "g"}},
+      "args": []
+    }}
+  }}
+}}"#;
+        assert_eq!(to_json(&parse_from_str(src).unwrap()), expected);
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        // the parser never produces these names, but trees can be built by hand
+        let expr = Expr::Quote(vec![
+            Expr::Constant(Atom::Keyword("say \"hi\"".to_string())),
+            Expr::Constant(Atom::Symbol("back\\slash\ttab".to_string())),
+        ]);
+        let expected = r#"{"quote": [
+  {"constant": {"keyword": "say \"hi\""}},
+  {"constant": {"symbol": "back\\slash\u0009tab"}}
+]}"#;
+        assert_eq!(to_json(&expr), expected);
+    }
+
+    /// Every form, with its expanded layout.
+    const EXPANDED: &[(&str, &str)] = &[
+        ("42", "42"),
+        ("(+ 1 2)", "(+\n  1\n  2)"),
+        ("(f)", "(f)"),
+        ("(if #t 1)", "(if #t\n  1)"),
+        (
+            "(if (= x 1) :a :b)",
+            "(if (=\n      x\n      1)\n  :a\n  :b)",
+        ),
+        ("'(1 (2 3))", "'(1\n  (2\n    3))"),
+        ("'()", "'()"),
+        (
+            "(let ((x 1) (yy (+ x 1))) (* x yy))",
+            "(let ((x 1)\n      (yy (+\n            x\n            1)))\n  (*\n    x\n    yy))",
+        ),
+        ("(let () 7)", "(let ()\n  7)"),
+        ("(fn (a b) (+ a b))", "(fn (a b)\n  (+\n    a\n    b))"),
+        ("(fn () 1)", "(fn ()\n  1)"),
+    ];
+
+    fn printer(layout: Layout) -> Printer {
+        Printer {
+            layout,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn expanded_breaks_every_list() {
+        for &(src, expanded) in EXPANDED {
+            let expr = parse_from_str(src).unwrap();
+            let printed = printer(Layout::Expanded).print(&expr);
+            assert_eq!(printed, expanded, "{}", src);
+            assert_eq!(parse_from_str(&printed), Ok(expr), "{}", src);
+        }
+    }
+
+    #[test]
+    fn compact_is_the_canonical_line() {
+        for &(src, expanded) in EXPANDED {
+            // the compact form of the expanded layout is the source again
+            let expr = parse_from_str(expanded).unwrap();
+            let printed = printer(Layout::Compact).print(&expr);
+            assert_eq!(printed, src);
+            assert_eq!(parse_from_str(&printed), Ok(expr), "{}", src);
+        }
+        // however long the line gets
+        let src = format!("(+ {})", "1 ".repeat(100).trim_end());
+        let expr = parse_from_str(&src).unwrap();
+        assert_eq!(printer(Layout::Compact).print(&expr), src);
+    }
+
+    #[test]
+    fn fitted_let_values_line_up() {
+        let printer = Printer {
+            width: 20,
+            ..Default::default()
+        };
+        let expr = parse_from_str("(let ((x 1) (long-name 2)) (+ x long-name))").unwrap();
+        let printed = printer.print(&expr);
+        assert_eq!(
+            printed,
+            "(let ((x         1)\n      (long-name 2))\n  (+ x long-name))"
+        );
+        assert_eq!(parse_from_str(&printed), Ok(expr));
+
+        // aligned, `(+ 1 2 3)` would run past the width
+        let expr = parse_from_str("(let ((x (+ 1 2 3)) (long-name 2)) x)").unwrap();
+        assert_eq!(
+            printer.print(&expr),
+            "(let ((x (+ 1 2 3))\n      (long-name 2))\n  x)"
+        );
+    }
+}
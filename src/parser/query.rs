@@ -0,0 +1,454 @@
+//! Structural search over parsed expressions.
+//!
+//! A query is written as an S-expression pattern that is matched against every
+//! sub-form of a tree, for example `(if _ _ ...)` or `(+ ?x ?x)`:
+//!
+//! * `_` matches any single form,
+//! * `?name` matches any single form, or a name bound by `let` or `fn`, and
+//!   captures it; using the same name twice requires both to be equal, where
+//!   a bound name equals a symbol of the same name,
+//! * `...` matches the remaining elements, and so must be the last element
+//!   of a list,
+//! * `if`, `let` and `fn` match the keyword at the head of a special form,
+//! * `'(...)` matches a quoted list element-wise,
+//! * any other atom matches itself; a symbol also matches a name bound by
+//!   `let` or `fn`, so `(let ((x _)) ...)` finds bindings of `x`.
+//!
+//! Expressions do not carry source spans yet, so a match is located by its
+//! path from the root: the list index of each step taken, written `/1/0`.
+//! The value of the second binding in `(let ((x 1) (y 2)) y)` is at `/1/1/1`.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{char, multispace0},
+    combinator::{all_consuming, cut, map, peek, verify},
+    error::{context, VerboseError},
+    multi::many0,
+    sequence::{preceded, terminated},
+    IResult,
+};
+
+use super::limits::Limits;
+use super::sexp::{atom_end, identifier, parse_atom, s_exp, Atom, Expr, RESERVED};
+use crate::error::{OxurError, Result};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    /// `_`
+    Any,
+    /// `?name`
+    Capture(String),
+    /// `...`
+    Rest,
+    /// The `if`, `let` or `fn` keyword heading a special form.
+    Keyword(String),
+    Atom(Atom),
+    /// `(p1 p2 ...)`
+    List(Vec<Pattern>),
+    /// `'(p1 p2 ...)`
+    Quote(Vec<Pattern>),
+}
+
+/// What a `?name` pattern captured.
+#[derive(Debug, Clone, Copy)]
+pub enum Captured<'a> {
+    Expr(&'a Expr),
+    /// A name bound by `let` or `fn`.
+    Name(&'a str),
+}
+
+impl<'a> Captured<'a> {
+    fn as_name(&self) -> Option<&'a str> {
+        match *self {
+            Captured::Name(name) => Some(name),
+            Captured::Expr(Expr::Constant(Atom::Symbol(symbol))) => Some(symbol),
+            Captured::Expr(_) => None,
+        }
+    }
+}
+
+impl<'a> PartialEq for Captured<'a> {
+    /// A bound name is equal to a reference to it, so `(let ((?x _)) ?x)`
+    /// finds a `let` that returns its own binding.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Captured::Expr(a), Captured::Expr(b)) => a == b,
+            _ => self.as_name().is_some() && self.as_name() == other.as_name(),
+        }
+    }
+}
+
+impl<'a> Display for Captured<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Captured::Expr(expr) => write!(f, "{}", expr),
+            Captured::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// A sub-form that matched a pattern.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Match<'a> {
+    pub path: Vec<usize>,
+    pub expr: &'a Expr,
+    pub captures: BTreeMap<String, Captured<'a>>,
+}
+
+/// The location of a sub-form, displayed as `/1/0`.
+pub struct Path<'p>(pub &'p [usize]);
+
+impl<'p> Display for Path<'p> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "/");
+        }
+        for index in self.0 {
+            write!(f, "/{}", index)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Match<'a> {
+    pub fn location(&self) -> Path<'_> {
+        Path(&self.path)
+    }
+}
+
+fn parse_pattern_list(i: &str) -> IResult<&str, Vec<Pattern>, VerboseError<&str>> {
+    s_exp(many0(parse_pattern))(i)
+}
+
+pub fn parse_pattern(i: &str) -> IResult<&str, Pattern, VerboseError<&str>> {
+    preceded(
+        multispace0,
+        alt((
+            map(
+                terminated(
+                    tag("..."),
+                    context("rest pattern", cut(peek(preceded(multispace0, char(')'))))),
+                ),
+                |_| Pattern::Rest,
+            ),
+            map(terminated(tag("_"), cut(atom_end)), |_| Pattern::Any),
+            map(
+                context(
+                    "capture",
+                    preceded(tag("?"), cut(terminated(identifier, atom_end))),
+                ),
+                |name: &str| Pattern::Capture(name.to_string()),
+            ),
+            map(
+                context("quote", preceded(tag("'"), cut(parse_pattern_list))),
+                Pattern::Quote,
+            ),
+            map(parse_pattern_list, Pattern::List),
+            map(parse_atom, Pattern::Atom),
+            map(
+                terminated(
+                    verify(identifier, |keyword: &str| RESERVED.contains(&keyword)),
+                    cut(atom_end),
+                ),
+                |keyword: &str| Pattern::Keyword(keyword.to_string()),
+            ),
+        )),
+    )(i)
+}
+
+/// Parse a pattern from a string, rejecting trailing input and anything
+/// beyond the default parsing limits.
+pub fn pattern_from_str(src: &str) -> Result<Pattern> {
+    Limits::default().check(src)?;
+    all_consuming(terminated(parse_pattern, multispace0))(src)
+        .map(|(_, pattern)| pattern)
+        .map_err(|e| OxurError::from_nom(src, e))
+}
+
+/// An element of an expression viewed as a plain list, the way it was
+/// written in the source.
+#[derive(Clone, Copy)]
+enum Element<'a> {
+    Keyword(&'static str),
+    /// A name bound by `let` or `fn`.
+    Name(&'a str),
+    /// The binding list of a `let`.
+    Bindings(&'a [(String, Expr)]),
+    Binding(&'a (String, Expr)),
+    /// The parameter list of a `fn`.
+    Params(&'a [String]),
+    Expr(&'a Expr),
+}
+
+fn elements(expr: &Expr) -> Vec<Element<'_>> {
+    match expr {
+        Expr::Application(head, tail) => core::iter::once(&**head)
+            .chain(tail)
+            .map(Element::Expr)
+            .collect(),
+        Expr::If(pred, true_branch) => vec![
+            Element::Keyword("if"),
+            Element::Expr(pred),
+            Element::Expr(true_branch),
+        ],
+        Expr::IfElse(pred, true_branch, false_branch) => vec![
+            Element::Keyword("if"),
+            Element::Expr(pred),
+            Element::Expr(true_branch),
+            Element::Expr(false_branch),
+        ],
+        Expr::Let(bindings, body) => vec![
+            Element::Keyword("let"),
+            Element::Bindings(bindings),
+            Element::Expr(body),
+        ],
+        Expr::Lambda(params, body) => vec![
+            Element::Keyword("fn"),
+            Element::Params(params),
+            Element::Expr(body),
+        ],
+        Expr::Quote(exprs) => exprs.iter().map(Element::Expr).collect(),
+        Expr::Constant(_) => Vec::new(),
+    }
+}
+
+/// The elements of the lists inside special forms that aren't expressions
+/// themselves.
+fn sub_elements(element: Element<'_>) -> Vec<Element<'_>> {
+    match element {
+        Element::Bindings(bindings) => bindings.iter().map(Element::Binding).collect(),
+        Element::Binding((name, value)) => vec![Element::Name(name), Element::Expr(value)],
+        Element::Params(params) => params.iter().map(|p| Element::Name(p)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn push_children<'a>(
+    element: Element<'a>,
+    path: Vec<usize>,
+    out: &mut Vec<(Vec<usize>, &'a Expr)>,
+) {
+    match element {
+        Element::Expr(child) => out.push((path, child)),
+        element => {
+            for (index, sub_element) in sub_elements(element).into_iter().enumerate() {
+                let mut path = path.clone();
+                path.push(index);
+                push_children(sub_element, path, out);
+            }
+        }
+    }
+}
+
+/// The sub-expressions of `expr`, each with the steps taken to reach it in a
+/// `Path`. That is a single index except inside the binding list of a `let`.
+pub(crate) fn children(expr: &Expr) -> Vec<(Vec<usize>, &Expr)> {
+    let mut out = Vec::new();
+    for (index, element) in elements(expr).into_iter().enumerate() {
+        push_children(element, vec![index], &mut out);
+    }
+    out
+}
+
+/// Capture `found` as `name`, unless `name` already holds something else.
+fn capture<'a>(
+    captures: &mut BTreeMap<String, Captured<'a>>,
+    name: &str,
+    found: Captured<'a>,
+) -> bool {
+    match captures.get(name) {
+        Some(previous) => *previous == found,
+        None => {
+            captures.insert(name.to_string(), found);
+            true
+        }
+    }
+}
+
+fn match_element<'a>(
+    pattern: &Pattern,
+    element: Element<'a>,
+    captures: &mut BTreeMap<String, Captured<'a>>,
+) -> bool {
+    match (pattern, element) {
+        (Pattern::Any, _) => true,
+        (_, Element::Expr(expr)) => match_expr(pattern, expr, captures),
+        (Pattern::Keyword(keyword), Element::Keyword(found)) => keyword == found,
+        (Pattern::Capture(capture_name), Element::Name(name)) => {
+            capture(captures, capture_name, Captured::Name(name))
+        }
+        (Pattern::Atom(Atom::Symbol(symbol)), Element::Name(name)) => symbol == name,
+        (Pattern::List(patterns), Element::Bindings(_))
+        | (Pattern::List(patterns), Element::Binding(_))
+        | (Pattern::List(patterns), Element::Params(_)) => {
+            match_elements(patterns, &sub_elements(element), captures)
+        }
+        _ => false,
+    }
+}
+
+fn match_elements<'a>(
+    patterns: &[Pattern],
+    elements: &[Element<'a>],
+    captures: &mut BTreeMap<String, Captured<'a>>,
+) -> bool {
+    match patterns.split_first() {
+        None => elements.is_empty(),
+        Some((Pattern::Rest, [])) => true,
+        Some((pattern, patterns)) => match elements.split_first() {
+            Some((element, elements)) => {
+                match_element(pattern, *element, captures)
+                    && match_elements(patterns, elements, captures)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_expr<'a>(
+    pattern: &Pattern,
+    expr: &'a Expr,
+    captures: &mut BTreeMap<String, Captured<'a>>,
+) -> bool {
+    match (pattern, expr) {
+        (Pattern::Any, _) => true,
+        (Pattern::Capture(name), _) => capture(captures, name, Captured::Expr(expr)),
+        (Pattern::Atom(atom), Expr::Constant(constant)) => atom == constant,
+        (Pattern::List(patterns), Expr::Application(..))
+        | (Pattern::List(patterns), Expr::If(..))
+        | (Pattern::List(patterns), Expr::IfElse(..))
+        | (Pattern::List(patterns), Expr::Let(..))
+        | (Pattern::List(patterns), Expr::Lambda(..))
+        | (Pattern::Quote(patterns), Expr::Quote(_)) => {
+            match_elements(patterns, &elements(expr), captures)
+        }
+        _ => false,
+    }
+}
+
+fn collect<'a>(pattern: &Pattern, expr: &'a Expr, path: &mut Vec<usize>, out: &mut Vec<Match<'a>>) {
+    let mut captures = BTreeMap::new();
+    if match_expr(pattern, expr, &mut captures) {
+        out.push(Match {
+            path: path.clone(),
+            expr,
+            captures,
+        });
+    }
+    for (steps, child) in children(expr) {
+        let depth = path.len();
+        path.extend(steps);
+        collect(pattern, child, path, out);
+        path.truncate(depth);
+    }
+}
+
+/// Find every sub-form of `root` matching `pattern`, outermost first.
+pub fn query<'a>(pattern: &Pattern, root: &'a Expr) -> Vec<Match<'a>> {
+    let mut out = Vec::new();
+    collect(pattern, root, &mut Vec::new(), &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::sexp::parse_from_str;
+
+    #[test]
+    fn rest_must_come_last() {
+        assert_eq!(
+            pattern_from_str("(if _ ...)"),
+            Ok(Pattern::List(vec![
+                Pattern::Keyword("if".to_string()),
+                Pattern::Any,
+                Pattern::Rest,
+            ]))
+        );
+        for src in &["'(?x ... ?x)", "(... _)", "...", "(if _ ..."] {
+            assert!(pattern_from_str(src).is_err(), "{}", src);
+        }
+        let error = pattern_from_str("(+ ... 1)").unwrap_err();
+        assert_eq!(error.message, "expected ')', found '1'");
+        assert_eq!(error.notes, vec!["while parsing rest pattern".to_string()]);
+    }
+
+    #[test]
+    fn wildcards_and_captures_end_at_a_delimiter() {
+        assert_eq!(
+            pattern_from_str("(?my-var ?empty? _ '(_))"),
+            Ok(Pattern::List(vec![
+                Pattern::Capture("my-var".to_string()),
+                Pattern::Capture("empty?".to_string()),
+                Pattern::Any,
+                Pattern::Quote(vec![Pattern::Any]),
+            ]))
+        );
+        for src in &["(_x 1)", "(+ _1)", "?1", "?", "(?a' 1)", "(if. _)"] {
+            assert!(pattern_from_str(src).is_err(), "{}", src);
+        }
+        let error = pattern_from_str("(_x ...)").unwrap_err();
+        assert_eq!(error.span.map(|span| span.start), Some(2));
+        // a capture name is read the same way as any other name
+        assert_eq!(
+            pattern_from_str("?a?b"),
+            Ok(Pattern::Capture("a?b".to_string()))
+        );
+    }
+
+    fn captures(pattern: &str, src: &str) -> Vec<Vec<(String, String)>> {
+        let pattern = pattern_from_str(pattern).unwrap();
+        let expr = parse_from_str(src).unwrap();
+        query(&pattern, &expr)
+            .iter()
+            .map(|found| {
+                found
+                    .captures
+                    .iter()
+                    .map(|(name, captured)| (name.clone(), captured.to_string()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|&(name, text)| (name.to_string(), text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn bound_names_can_be_captured() {
+        assert_eq!(
+            captures("(let ((?n ?v)) ...)", "(let ((x (+ 1 2))) x)"),
+            vec![pairs(&[("n", "x"), ("v", "(+ 1 2)")])]
+        );
+        assert_eq!(
+            captures("(fn (?a ?b) _)", "(fn (x y) (+ x y))"),
+            vec![pairs(&[("a", "x"), ("b", "y")])]
+        );
+    }
+
+    #[test]
+    fn a_captured_name_equals_a_reference_to_it() {
+        let src = "(let ((x 1)) (let ((y 2)) x))";
+        assert_eq!(
+            captures("(let ((?n _)) ?n)", src),
+            Vec::<Vec<(String, String)>>::new()
+        );
+        let src = "(let ((x 1)) x)";
+        assert_eq!(
+            captures("(let ((?n _)) ?n)", src),
+            vec![pairs(&[("n", "x")])]
+        );
+        assert_eq!(captures("(fn (?p) (+ ?p 1))", "(fn (n) (+ n 1))").len(), 1);
+    }
+}
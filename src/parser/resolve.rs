@@ -0,0 +1,279 @@
+//! Static name resolution.
+//!
+//! The interpreter only notices an unbound name when it evaluates it, so a
+//! typo on a branch that is never taken goes unreported. `resolve` checks every
+//! symbol against the bindings around it before anything runs, with the same
+//! scoping as the interpreter: a `let` binding is in scope for the bindings
+//! after it and for the body, and the parameters of a `fn` for its body.
+//! Quoted lists are data, so the names in them are not references.
+//!
+//! It reports:
+//!
+//! * `E0300`, an error, for a symbol with no binding in scope,
+//! * `E0301`, an error, for a `fn` naming the same parameter twice (the parser
+//!   rejects this already, but trees can be built by hand),
+//! * `E0302`, a warning, for a binding that shadows another in scope.
+//!
+//! Expressions do not carry source spans yet, so like lints, each report gives
+//! the path of the offending name.
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use super::query::Path;
+use super::sexp::{Atom, Expr};
+use super::visit::{self, Visit};
+use crate::error::{ErrorCode, OxurError, Severity};
+
+/// A name bound by `let` or `fn`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Binding<'ast> {
+    pub name: &'ast str,
+    /// Where the name is written, in the same form as `query::Match::path`.
+    pub path: Vec<usize>,
+    /// How many symbols refer to this binding.
+    pub uses: usize,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Resolution<'ast> {
+    /// Every binding in the tree, in the order they come into scope.
+    pub bindings: Vec<Binding<'ast>>,
+    /// Errors and warnings, in source order.
+    pub errors: Vec<OxurError>,
+}
+
+impl<'ast> Resolution<'ast> {
+    /// Whether any report is an error rather than a warning.
+    pub fn has_errors(&self) -> bool {
+        self.errors
+            .iter()
+            .any(|error| error.severity == Severity::Error)
+    }
+}
+
+#[derive(Default)]
+struct Resolver<'ast> {
+    path: Vec<usize>,
+    bindings: Vec<Binding<'ast>>,
+    /// Indexes into `bindings` of the names in scope, innermost last.
+    scope: Vec<usize>,
+    /// Where the parameters of the `fn` being entered start in `scope`, until
+    /// its body is reached.
+    params: Option<usize>,
+    reports: Vec<(Vec<usize>, OxurError)>,
+}
+
+impl<'ast> Resolver<'ast> {
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scope
+            .iter()
+            .rev()
+            .copied()
+            .find(|&index| self.bindings[index].name == name)
+    }
+
+    fn report(&mut self, error: OxurError) {
+        self.reports.push((self.path.clone(), error));
+    }
+
+    fn refer(&mut self, name: &str) {
+        match self.lookup(name) {
+            Some(index) => self.bindings[index].uses += 1,
+            None => {
+                let message = format!("unbound symbol `{}` at {}", name, Path(&self.path));
+                self.report(OxurError::new(ErrorCode::UnboundSymbol, message));
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Resolver<'ast> {
+    fn visit_expr(&mut self, node: &'ast Expr) {
+        self.params = None;
+        match node {
+            Expr::Constant(Atom::Symbol(name)) => self.refer(name),
+            Expr::Quote(_) => {}
+            Expr::Let(..) | Expr::Lambda(..) => {
+                let outer = self.scope.len();
+                if let Expr::Lambda(..) = node {
+                    self.params = Some(outer);
+                }
+                visit::visit_expr(self, node);
+                self.scope.truncate(outer);
+            }
+            _ => visit::visit_expr(self, node),
+        }
+    }
+
+    fn visit_binding(&mut self, name: &'ast str) {
+        if let Some(earlier) = self.lookup(name) {
+            let is_param =
+                matches!(self.params, Some(start) if self.scope[start..].contains(&earlier));
+            let error = if is_param {
+                OxurError::new(
+                    ErrorCode::DuplicateParameter,
+                    format!(
+                        "parameter `{}` is named twice at {}",
+                        name,
+                        Path(&self.path)
+                    ),
+                )
+            } else {
+                OxurError::new(
+                    ErrorCode::Shadowing,
+                    format!(
+                        "`{}` shadows an earlier binding at {}",
+                        name,
+                        Path(&self.path)
+                    ),
+                )
+                .with_severity(Severity::Warning)
+            };
+            let note = format!(
+                "`{}` was bound before at {}",
+                name,
+                Path(&self.bindings[earlier].path)
+            );
+            self.report(error.with_note(note));
+        }
+        self.scope.push(self.bindings.len());
+        self.bindings.push(Binding {
+            name,
+            path: self.path.clone(),
+            uses: 0,
+        });
+    }
+
+    fn enter_child(&mut self, steps: &[usize]) {
+        self.path.extend_from_slice(steps);
+    }
+
+    fn leave_child(&mut self, steps: &[usize]) {
+        self.path.truncate(self.path.len() - steps.len());
+    }
+}
+
+/// Resolve every symbol in `expr` to the binding it refers to.
+pub fn resolve(expr: &Expr) -> Resolution<'_> {
+    let mut resolver = Resolver::default();
+    resolver.visit_expr(expr);
+    let mut reports = resolver.reports;
+    reports.sort_by(|a, b| a.0.cmp(&b.0));
+    Resolution {
+        bindings: resolver.bindings,
+        errors: reports.into_iter().map(|(_, error)| error).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::sexp::parse_from_str;
+    use alloc::boxed::Box;
+    use alloc::string::{String, ToString};
+    use alloc::vec;
+
+    fn messages(src: &str) -> Vec<String> {
+        resolve(&parse_from_str(src).unwrap())
+            .errors
+            .into_iter()
+            .map(|error| error.message)
+            .collect()
+    }
+
+    #[test]
+    fn bound_names_resolve() {
+        let src = "(let ((x 1) (f (fn (y) (+ x y)))) (f x))";
+        let expr = parse_from_str(src).unwrap();
+        let resolution = resolve(&expr);
+        assert_eq!(resolution.errors, Vec::new());
+        let uses: Vec<(&str, usize)> = resolution
+            .bindings
+            .iter()
+            .map(|binding| (binding.name, binding.uses))
+            .collect();
+        assert_eq!(uses, vec![("x", 2), ("y", 1), ("f", 1)]);
+        assert_eq!(resolution.bindings[1].path, vec![1, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn unbound_symbols_are_errors_on_any_branch() {
+        let expr = parse_from_str("(if #t 1 (+ y 1))").unwrap();
+        let resolution = resolve(&expr);
+        assert!(resolution.has_errors());
+        assert_eq!(resolution.errors[0].code, ErrorCode::UnboundSymbol);
+        assert_eq!(resolution.errors[0].message, "unbound symbol `y` at /3/1");
+    }
+
+    #[test]
+    fn scopes_end_with_their_form() {
+        assert_eq!(
+            messages("(let ((x 1) (y (let ((z x)) z))) (+ x y z))"),
+            vec!["unbound symbol `z` at /2/3"]
+        );
+        assert_eq!(
+            messages("(let ((x x)) 1)"),
+            vec!["unbound symbol `x` at /1/0/1"]
+        );
+        assert_eq!(messages("((fn (x) x) x)"), vec!["unbound symbol `x` at /1"]);
+    }
+
+    #[test]
+    fn quoted_names_are_not_references() {
+        assert_eq!(messages("'(x y)"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn shadowing_is_a_warning() {
+        let src = "(let ((x 1) (x (+ x 1))) (fn (x) x))";
+        let expr = parse_from_str(src).unwrap();
+        let resolution = resolve(&expr);
+        assert!(!resolution.has_errors());
+        let reports: Vec<(ErrorCode, Severity, String, Vec<String>)> = resolution
+            .errors
+            .into_iter()
+            .map(|error| (error.code, error.severity, error.message, error.notes))
+            .collect();
+        assert_eq!(
+            reports,
+            vec![
+                (
+                    ErrorCode::Shadowing,
+                    Severity::Warning,
+                    "`x` shadows an earlier binding at /1/1/0".to_string(),
+                    vec!["`x` was bound before at /1/0/0".to_string()],
+                ),
+                (
+                    ErrorCode::Shadowing,
+                    Severity::Warning,
+                    "`x` shadows an earlier binding at /2/1/0".to_string(),
+                    vec!["`x` was bound before at /1/1/0".to_string()],
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_parameters_are_errors() {
+        // the parser rejects these, so build the tree by hand
+        let expr = Expr::Let(
+            vec![("x".to_string(), Expr::Constant(Atom::Num(1)))],
+            Box::new(Expr::Lambda(
+                vec!["x".to_string(), "y".to_string(), "y".to_string()],
+                Box::new(Expr::Constant(Atom::Symbol("y".to_string()))),
+            )),
+        );
+        let resolution = resolve(&expr);
+        assert!(resolution.has_errors());
+        let codes: Vec<ErrorCode> = resolution.errors.iter().map(|e| e.code).collect();
+        assert_eq!(
+            codes,
+            vec![ErrorCode::Shadowing, ErrorCode::DuplicateParameter]
+        );
+        assert_eq!(
+            resolution.errors[1].message,
+            "parameter `y` is named twice at /2/1/2"
+        );
+    }
+}
@@ -2,17 +2,28 @@
 //! parser and tiny [lisp](https://en.wikipedia.org/wiki/Lisp_(programming_language)) interpreter.
 //! Lisp is a simple type of language made up of Atoms and Lists, forming easily parsable trees.
 
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::{alpha1, char, digit1, multispace0, multispace1, one_of},
-    combinator::{cut, map, map_res, opt},
-    error::{context, VerboseError},
+    character::complete::{
+        alpha1, alphanumeric1, char, digit1, multispace0, multispace1, none_of, one_of,
+    },
+    combinator::{cut, map, map_res, not, opt, recognize, verify},
+    error::{context, VerboseError, VerboseErrorKind},
     multi::many0,
-    sequence::{delimited, preceded, terminated, tuple},
+    sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
 };
 
+use super::interp::{Env, Interpreter, Value};
+use super::limits::Parser;
+use crate::error::Result;
+
 /// We start by defining the types that define the shape of data that we want.
 /// In this case, we want something tree-like
 
@@ -24,6 +35,10 @@ pub enum BuiltIn {
     Times,
     Divide,
     Equal,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
     Not,
 }
 
@@ -36,6 +51,8 @@ pub enum Atom {
     Keyword(String),
     Boolean(bool),
     BuiltIn(BuiltIn),
+    /// A name bound by `let` or `fn`.
+    Symbol(String),
 }
 
 /// The remaining half is Lists. We implement these as recursive Expressions.
@@ -59,13 +76,17 @@ pub enum Expr {
     IfElse(Box<Expr>, Box<Expr>, Box<Expr>),
     /// '(3 (if (+ 3 3) 4 5) 7)
     Quote(Vec<Expr>),
+    /// (let ((name value) ...) body)
+    Let(Vec<(String, Expr)>, Box<Expr>),
+    /// (fn (param ...) body)
+    Lambda(Vec<String>, Box<Expr>),
 }
 
 /// Continuing the trend of starting from the simplest piece and building up,
 /// we start by creating a parser for the built-in operator functions.
 fn parse_builtin_op<'a>(i: &'a str) -> IResult<&'a str, BuiltIn, VerboseError<&'a str>> {
     // one_of matches one of the characters we give it
-    let (i, t) = one_of("+-*/=")(i)?;
+    let (i, t) = one_of("+-*/=<>")(i)?;
 
     // because we are matching single character tokens, we can do the matching logic
     // on the returned value
@@ -77,6 +98,8 @@ fn parse_builtin_op<'a>(i: &'a str) -> IResult<&'a str, BuiltIn, VerboseError<&'
             '*' => BuiltIn::Times,
             '/' => BuiltIn::Divide,
             '=' => BuiltIn::Equal,
+            '<' => BuiltIn::Less,
+            '>' => BuiltIn::Greater,
             _ => unreachable!(),
         },
     ))
@@ -86,10 +109,12 @@ fn parse_builtin<'a>(i: &'a str) -> IResult<&'a str, BuiltIn, VerboseError<&'a s
     // alt gives us the result of first parser that succeeds, of the series of
     // parsers we give it
     alt((
-        parse_builtin_op,
         // map lets us process the parsed output, in this case we know what we parsed,
-        // so we ignore the input and return the BuiltIn directly
-        map(tag("not"), |_| BuiltIn::Not),
+        // so we ignore the input and return the BuiltIn directly; the two character
+        // operators have to come first, or `<` would match the start of `<=`
+        map(tag("<="), |_| BuiltIn::LessEqual),
+        map(tag(">="), |_| BuiltIn::GreaterEqual),
+        parse_builtin_op,
     ))(i)
 }
 
@@ -114,28 +139,66 @@ fn parse_keyword<'a>(i: &'a str) -> IResult<&'a str, Atom, VerboseError<&'a str>
     )(i)
 }
 
-/// Next up is number parsing. We're keeping it simple here by accepting any number (> 1)
-/// of digits but ending the program if it doesn't fit into an i32.
-fn parse_num<'a>(i: &'a str) -> IResult<&'a str, Atom, VerboseError<&'a str>> {
+/// Names are a letter followed by letters, digits, and `-_?!`, so `empty?` and
+/// `set-car!` are names too.
+pub(crate) fn identifier(i: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    recognize(pair(
+        alpha1,
+        many0(alt((alphanumeric1, tag("-"), tag("_"), tag("?"), tag("!")))),
+    ))(i)
+}
+
+/// The special form keywords can't be bound, and neither can `not`: it always
+/// parses as the built-in, so a binding of it could never be referred to.
+pub(crate) const RESERVED: &[&str] = &["if", "let", "fn", "not"];
+
+pub(crate) fn parse_name(i: &str) -> IResult<&str, &str, VerboseError<&str>> {
+    verify(identifier, |name: &str| !RESERVED.contains(&name))(i)
+}
+
+/// A name is a symbol unless it names a built-in. Parsing `not` here rather than
+/// with the operators keeps it from matching the start of a name like `nothing`.
+fn parse_symbol(i: &str) -> IResult<&str, Atom, VerboseError<&str>> {
     alt((
-        map_res(digit1, |digit_str: &str| {
-            digit_str.parse::<i32>().map(Atom::Num)
-        }),
-        map(preceded(tag("-"), digit1), |digit_str: &str| {
-            Atom::Num(-1 * digit_str.parse::<i32>().unwrap())
+        map(verify(identifier, |name: &str| name == "not"), |_| {
+            Atom::BuiltIn(BuiltIn::Not)
         }),
+        map(parse_name, |name: &str| Atom::Symbol(name.to_string())),
     ))(i)
 }
 
+/// Next up is number parsing. We're keeping it simple here by accepting any number (> 1)
+/// of digits, optionally negated, and rejecting the input if it doesn't fit into an i32.
+/// The sign is parsed together with the digits so that `i32::MIN` is still accepted.
+fn parse_num<'a>(i: &'a str) -> IResult<&'a str, Atom, VerboseError<&'a str>> {
+    map_res(
+        recognize(preceded(opt(tag("-")), digit1)),
+        |digit_str: &str| digit_str.parse::<i32>().map(Atom::Num),
+    )(i)
+}
+
+/// An atom has to end at whitespace, a paren, a quote, or the end of the input.
+/// Otherwise `1x` would quietly parse as `1` followed by `x`, and `+x` as `+` and `x`.
+pub(crate) fn atom_end(i: &str) -> IResult<&str, (), VerboseError<&str>> {
+    not(none_of(" \t\r\n()'"))(i)
+}
+
 /// Now we take all these simple parsers and connect them.
 /// We can now parse half of our language!
-fn parse_atom<'a>(i: &'a str) -> IResult<&'a str, Atom, VerboseError<&'a str>> {
-    alt((
-        parse_num,
-        parse_bool,
-        map(parse_builtin, Atom::BuiltIn),
-        parse_keyword,
-    ))(i)
+///
+/// Once an atom has been recognized, a missing delimiter after it is an error
+/// at that point, so `cut` stops the callers from trying other forms.
+pub(crate) fn parse_atom<'a>(i: &'a str) -> IResult<&'a str, Atom, VerboseError<&'a str>> {
+    terminated(
+        alt((
+            parse_num,
+            parse_bool,
+            map(parse_builtin, Atom::BuiltIn),
+            parse_keyword,
+            parse_symbol,
+        )),
+        cut(atom_end),
+    )(i)
 }
 
 /// We then add the Expr layer on top
@@ -150,7 +213,9 @@ fn parse_constant<'a>(i: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str
 ///
 /// Unlike the previous functions, this function doesn't take or consume input, instead it
 /// takes a parsing function and returns a new parsing function.
-fn s_exp<'a, O1, F>(inner: F) -> impl Fn(&'a str) -> IResult<&'a str, O1, VerboseError<&'a str>>
+pub(crate) fn s_exp<'a, O1, F>(
+    inner: F,
+) -> impl Fn(&'a str) -> IResult<&'a str, O1, VerboseError<&'a str>>
 where
     F: Fn(&'a str) -> IResult<&'a str, O1, VerboseError<&'a str>>,
 {
@@ -211,11 +276,67 @@ fn parse_if<'a>(i: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
     s_exp(if_inner)(i)
 }
 
+/// `let` binds each name in turn, so later bindings can refer to earlier ones:
+/// `(let ((x 1) (y (+ x 1))) (* x y))`. Like `if`, the keyword must be followed
+/// by whitespace so that names such as `letter` still parse as symbols.
+fn parse_let(i: &str) -> IResult<&str, Expr, VerboseError<&str>> {
+    let binding = preceded(
+        multispace0,
+        s_exp(map(tuple((parse_name, parse_expr)), |(name, value)| {
+            (name.to_string(), value)
+        })),
+    );
+    let let_inner = context(
+        "let expression",
+        map(
+            preceded(
+                terminated(tag("let"), multispace1),
+                cut(tuple((s_exp(many0(binding)), parse_expr))),
+            ),
+            |(bindings, body)| Expr::Let(bindings, Box::new(body)),
+        ),
+    );
+    s_exp(let_inner)(i)
+}
+
+/// The parameter names of a `fn`. Like Rust's `|x, x|`, naming the same
+/// parameter twice is an error rather than letting the last one win.
+fn parse_params(i: &str) -> IResult<&str, Vec<String>, VerboseError<&str>> {
+    let mut params: Vec<String> = Vec::new();
+    let mut rest = i;
+    loop {
+        let (start, _) = multispace0(rest)?;
+        let (after, name) = match parse_name(start) {
+            Ok(parsed) => parsed,
+            Err(nom::Err::Error(_)) => return Ok((rest, params)),
+            Err(e) => return Err(e),
+        };
+        if params.iter().any(|param| param == name) {
+            return Err(nom::Err::Failure(VerboseError {
+                errors: vec![(start, VerboseErrorKind::Context("duplicate parameter"))],
+            }));
+        }
+        params.push(name.to_string());
+        rest = after;
+    }
+}
+
+/// `fn` makes an anonymous function of its parameter names: `(fn (x y) (+ x y))`.
+fn parse_fn(i: &str) -> IResult<&str, Expr, VerboseError<&str>> {
+    let fn_inner = context(
+        "fn expression",
+        map(
+            preceded(
+                terminated(tag("fn"), multispace1),
+                cut(tuple((s_exp(parse_params), parse_expr))),
+            ),
+            |(params, body)| Expr::Lambda(params, Box::new(body)),
+        ),
+    );
+    s_exp(fn_inner)(i)
+}
+
 /// A quoted S-expression is list data structure.
-///
-/// This example doesn't have the symbol atom, but by adding variables and changing
-/// the definition of quote to not always be around an S-expression, we'd get them
-/// naturally.
 fn parse_quote<'a>(i: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
     // this should look very straight-forward after all we've done:
     // we find the `'` (quote) character, use cut to say that we're unambiguously
@@ -227,14 +348,29 @@ fn parse_quote<'a>(i: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>>
 }
 
 /// We tie them all together again, making a top-level expression parser!
-
-pub fn parse_expr<'a>(i: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
+///
+/// This recurses once per level of nesting and accepts trailing input, so it
+/// is only used through `limits::Parser`, which checks the input first.
+pub(crate) fn parse_expr<'a>(i: &'a str) -> IResult<&'a str, Expr, VerboseError<&'a str>> {
     preceded(
         multispace0,
-        alt((parse_constant, parse_application, parse_if, parse_quote)),
+        alt((
+            parse_constant,
+            parse_application,
+            parse_if,
+            parse_let,
+            parse_fn,
+            parse_quote,
+        )),
     )(i)
 }
 
+/// Parse a complete source string holding a single expression, within the
+/// default `limits::Limits`.
+pub fn parse_from_str(src: &str) -> Result<Expr> {
+    Parser::default().parse(src)
+}
+
 /// And that's it!
 /// We can now parse our entire lisp language.
 ///
@@ -260,11 +396,88 @@ fn get_bool_from_expr(e: Expr) -> Option<bool> {
     }
 }
 
+/// Applies a built-in operator to already reduced arguments.
+/// Arithmetic is checked, so overflow and division by zero fail the evaluation
+/// rather than panicking. Comparisons hold when every adjacent pair of arguments
+/// is ordered, so `(< 1 2 3)` is true.
+pub(crate) fn apply_builtin(bi: BuiltIn, reduced_tail: Vec<Expr>) -> Option<Atom> {
+    Some(match bi {
+        BuiltIn::Plus => Atom::Num(
+            reduced_tail
+                .into_iter()
+                .map(get_num_from_expr)
+                .collect::<Option<Vec<i32>>>()?
+                .into_iter()
+                .try_fold(0, i32::checked_add)?,
+        ),
+        BuiltIn::Times => Atom::Num(
+            reduced_tail
+                .into_iter()
+                .map(get_num_from_expr)
+                .collect::<Option<Vec<i32>>>()?
+                .into_iter()
+                .try_fold(1, i32::checked_mul)?,
+        ),
+        BuiltIn::Equal => Atom::Boolean(
+            reduced_tail
+                .iter()
+                .zip(reduced_tail.iter().skip(1))
+                .all(|(a, b)| a == b),
+        ),
+        BuiltIn::Less | BuiltIn::Greater | BuiltIn::LessEqual | BuiltIn::GreaterEqual => {
+            let nums = reduced_tail
+                .into_iter()
+                .map(get_num_from_expr)
+                .collect::<Option<Vec<i32>>>()?;
+            Atom::Boolean(nums.windows(2).all(|pair| match bi {
+                BuiltIn::Less => pair[0] < pair[1],
+                BuiltIn::Greater => pair[0] > pair[1],
+                BuiltIn::LessEqual => pair[0] <= pair[1],
+                _ => pair[0] >= pair[1],
+            }))
+        }
+        BuiltIn::Not => {
+            if reduced_tail.len() != 1 {
+                return None;
+            } else {
+                Atom::Boolean(!get_bool_from_expr(reduced_tail.first().cloned().unwrap())?)
+            }
+        }
+        BuiltIn::Minus => Atom::Num(if let Some(first_elem) = reduced_tail.first().cloned() {
+            let fe = get_num_from_expr(first_elem)?;
+            reduced_tail
+                .into_iter()
+                .map(get_num_from_expr)
+                .collect::<Option<Vec<i32>>>()?
+                .into_iter()
+                .skip(1)
+                .try_fold(fe, i32::checked_sub)?
+        } else {
+            Default::default()
+        }),
+        BuiltIn::Divide => Atom::Num(if let Some(first_elem) = reduced_tail.first().cloned() {
+            let fe = get_num_from_expr(first_elem)?;
+            reduced_tail
+                .into_iter()
+                .map(get_num_from_expr)
+                .collect::<Option<Vec<i32>>>()?
+                .into_iter()
+                .skip(1)
+                .try_fold(fe, i32::checked_div)?
+        } else {
+            Default::default()
+        }),
+    })
+}
+
 /// This function tries to reduce the AST.
 /// This has to return an Expression rather than an Atom because quoted s_expressions
-/// can't be reduced
+/// can't be reduced.
+/// It works on closed expressions only: anything involving names (`let`, `fn`, or a
+/// symbol) needs an environment, so it fails here and is left to `interp`.
 pub fn eval_expression(e: Expr) -> Option<Expr> {
     match e {
+        Expr::Constant(Atom::Symbol(_)) | Expr::Let(..) | Expr::Lambda(..) => None,
         // Constants and quoted s-expressions are our base-case
         Expr::Constant(_) | Expr::Quote(_) => Some(e),
         // we then recursively `eval_expression` in the context of our special forms
@@ -292,67 +505,7 @@ pub fn eval_expression(e: Expr) -> Option<Expr> {
                 .map(|expr| eval_expression(expr))
                 .collect::<Option<Vec<Expr>>>()?;
             if let Expr::Constant(Atom::BuiltIn(bi)) = reduced_head {
-                Some(Expr::Constant(match bi {
-                    BuiltIn::Plus => Atom::Num(
-                        reduced_tail
-                            .into_iter()
-                            .map(get_num_from_expr)
-                            .collect::<Option<Vec<i32>>>()?
-                            .into_iter()
-                            .sum(),
-                    ),
-                    BuiltIn::Times => Atom::Num(
-                        reduced_tail
-                            .into_iter()
-                            .map(get_num_from_expr)
-                            .collect::<Option<Vec<i32>>>()?
-                            .into_iter()
-                            .product(),
-                    ),
-                    BuiltIn::Equal => Atom::Boolean(
-                        reduced_tail
-                            .iter()
-                            .zip(reduced_tail.iter().skip(1))
-                            .all(|(a, b)| a == b),
-                    ),
-                    BuiltIn::Not => {
-                        if reduced_tail.len() != 1 {
-                            return None;
-                        } else {
-                            Atom::Boolean(!get_bool_from_expr(
-                                reduced_tail.first().cloned().unwrap(),
-                            )?)
-                        }
-                    }
-                    BuiltIn::Minus => {
-                        Atom::Num(if let Some(first_elem) = reduced_tail.first().cloned() {
-                            let fe = get_num_from_expr(first_elem)?;
-                            reduced_tail
-                                .into_iter()
-                                .map(get_num_from_expr)
-                                .collect::<Option<Vec<i32>>>()?
-                                .into_iter()
-                                .skip(1)
-                                .fold(fe, |a, b| a - b)
-                        } else {
-                            Default::default()
-                        })
-                    }
-                    BuiltIn::Divide => {
-                        Atom::Num(if let Some(first_elem) = reduced_tail.first().cloned() {
-                            let fe = get_num_from_expr(first_elem)?;
-                            reduced_tail
-                                .into_iter()
-                                .map(get_num_from_expr)
-                                .collect::<Option<Vec<i32>>>()?
-                                .into_iter()
-                                .skip(1)
-                                .fold(fe, |a, b| a / b)
-                        } else {
-                            Default::default()
-                        })
-                    }
-                }))
+                apply_builtin(bi, reduced_tail).map(Expr::Constant)
             } else {
                 None
             }
@@ -361,9 +514,65 @@ pub fn eval_expression(e: Expr) -> Option<Expr> {
 }
 
 /// And we add one more top-level function to tie everything together, letting
-/// us call eval on a string directly
-pub fn eval_from_str(src: &str) -> Result<Expr, String> {
-    parse_expr(src)
-        .map_err(|e: nom::Err<VerboseError<&str>>| format!("{:#?}", e))
-        .and_then(|(_, exp)| eval_expression(exp).ok_or("Eval failed".to_string()))
+/// us call eval on a string directly. This goes through the interpreter in
+/// `interp`, so names, `let`, and `fn` work here too.
+pub fn eval_from_str(src: &str) -> Result<Value> {
+    let exp = parse_from_str(src)?;
+    Interpreter::default().eval(&exp, &Env::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ErrorCode, Span};
+
+    #[test]
+    fn eval_from_str_rejects_trailing_input() {
+        let error = eval_from_str("(+ 1 2) )").unwrap_err();
+        assert_eq!(error.code, ErrorCode::TrailingInput);
+        assert_eq!(eval_from_str("(+ 1 2)\n"), Ok(Value::Atom(Atom::Num(3))));
+    }
+
+    #[test]
+    fn atoms_must_end_at_a_delimiter() {
+        for src in &[
+            "(+ 1x)",
+            "(+x 1)",
+            "#tfoo",
+            "(+ 1-)",
+            "(f :a1)",
+            "(<=> 1 2)",
+        ] {
+            let error = parse_from_str(src).unwrap_err();
+            assert_eq!(error.code, ErrorCode::Syntax, "{}", src);
+        }
+        let error = parse_from_str("(+ 1x)").unwrap_err();
+        assert_eq!(error.span.map(|span| span.start), Some(4));
+
+        for src in &["(+ 1 2)", "(+(- 1)'(2))", "(not #t)", "(<= 1 2)", "-1"] {
+            assert!(parse_from_str(src).is_ok(), "{}", src);
+        }
+    }
+
+    #[test]
+    fn not_cannot_be_bound() {
+        for src in &["((fn (not) (not #t)) 5)", "(let ((not 1)) not)"] {
+            let error = parse_from_str(src).unwrap_err();
+            assert_eq!(error.code, ErrorCode::Syntax, "{}", src);
+        }
+        assert!(parse_from_str("(let ((nothing #t)) (not nothing))").is_ok());
+    }
+
+    #[test]
+    fn parameters_must_differ() {
+        let src = "((fn (x y x) x) 1 2 3)";
+        let error = parse_from_str(src).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Syntax);
+        assert_eq!(error.message, "invalid duplicate parameter");
+        assert_eq!(error.span, Some(Span { start: 10, end: 11 }));
+        assert!(parse_from_str("(fn (x y) x)").is_ok());
+        assert!(parse_from_str("(fn () 1)").is_ok());
+        // a sequential `let` may rebind a name; that is shadowing, not a clash
+        assert!(parse_from_str("(let ((x 1) (x (+ x 1))) x)").is_ok());
+    }
 }
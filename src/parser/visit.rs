@@ -0,0 +1,217 @@
+//! Syntax tree traversal to walk a shared borrow of an `Expr` tree.
+//!
+//! Each method of the [`Visit`] trait is a hook that can be overridden to
+//! customize the behavior when visiting the corresponding type of node. By
+//! default, every method recursively visits the substructure of the input by
+//! invoking the right visitor method of each of its fields.
+//!
+//! This follows the same shape as `syn::visit`: the trait methods delegate to
+//! free functions of the same name, so an override can still fall back to the
+//! default traversal by calling e.g. `visit::visit_expr(self, node)`.
+//!
+//! Visitors that need to know where they are can track the path from the root
+//! through `enter_child` and `leave_child`, which bracket every step down the
+//! tree with the same list indexes as a `query::Path`.
+
+use super::sexp::{Atom, BuiltIn, Expr};
+
+pub trait Visit<'ast> {
+    fn visit_expr(&mut self, node: &'ast Expr) {
+        visit_expr(self, node)
+    }
+
+    fn visit_atom(&mut self, node: &'ast Atom) {
+        visit_atom(self, node)
+    }
+
+    fn visit_builtin(&mut self, node: &'ast BuiltIn) {
+        visit_builtin(self, node)
+    }
+
+    /// A name bound by `let` or `fn`, visited where it comes into scope: just
+    /// after the value of its `let` binding, or before the body of its `fn`.
+    fn visit_binding(&mut self, _name: &'ast str) {}
+
+    /// Called before stepping into a child, with the list indexes that lead
+    /// to it: one index, or three for the value of a `let` binding.
+    fn enter_child(&mut self, _steps: &[usize]) {}
+
+    /// Called after the child `enter_child` announced has been visited.
+    fn leave_child(&mut self, _steps: &[usize]) {}
+}
+
+fn visit_child<'ast, V>(v: &mut V, steps: &[usize], node: &'ast Expr)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    v.enter_child(steps);
+    v.visit_expr(node);
+    v.leave_child(steps);
+}
+
+fn visit_binding_at<'ast, V>(v: &mut V, steps: &[usize], name: &'ast str)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    v.enter_child(steps);
+    v.visit_binding(name);
+    v.leave_child(steps);
+}
+
+pub fn visit_expr<'ast, V>(v: &mut V, node: &'ast Expr)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    match node {
+        Expr::Constant(atom) => v.visit_atom(atom),
+        Expr::Application(head, tail) => {
+            visit_child(v, &[0], head);
+            for (i, expr) in tail.iter().enumerate() {
+                visit_child(v, &[i + 1], expr);
+            }
+        }
+        Expr::If(pred, true_branch) => {
+            visit_child(v, &[1], pred);
+            visit_child(v, &[2], true_branch);
+        }
+        Expr::IfElse(pred, true_branch, false_branch) => {
+            visit_child(v, &[1], pred);
+            visit_child(v, &[2], true_branch);
+            visit_child(v, &[3], false_branch);
+        }
+        Expr::Quote(exprs) => {
+            for (i, expr) in exprs.iter().enumerate() {
+                visit_child(v, &[i], expr);
+            }
+        }
+        Expr::Let(bindings, body) => {
+            for (i, (name, value)) in bindings.iter().enumerate() {
+                visit_child(v, &[1, i, 1], value);
+                visit_binding_at(v, &[1, i, 0], name);
+            }
+            visit_child(v, &[2], body);
+        }
+        Expr::Lambda(params, body) => {
+            for (i, param) in params.iter().enumerate() {
+                visit_binding_at(v, &[1, i], param);
+            }
+            visit_child(v, &[2], body);
+        }
+    }
+}
+
+pub fn visit_atom<'ast, V>(v: &mut V, node: &'ast Atom)
+where
+    V: Visit<'ast> + ?Sized,
+{
+    if let Atom::BuiltIn(builtin) = node {
+        v.visit_builtin(builtin);
+    }
+}
+
+pub fn visit_builtin<'ast, V>(_v: &mut V, _node: &'ast BuiltIn)
+where
+    V: Visit<'ast> + ?Sized,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::query::{children, Path};
+    use crate::parser::sexp::parse_from_str;
+    use alloc::format;
+    use alloc::string::String;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// Records each node visited, as `path node`.
+    #[derive(Default)]
+    struct Recorder {
+        path: Vec<usize>,
+        seen: Vec<String>,
+    }
+
+    impl<'ast> Visit<'ast> for Recorder {
+        fn visit_expr(&mut self, node: &'ast Expr) {
+            self.seen.push(format!("{} {}", Path(&self.path), node));
+            visit_expr(self, node)
+        }
+
+        fn visit_binding(&mut self, name: &'ast str) {
+            self.seen
+                .push(format!("{} binds {}", Path(&self.path), name));
+        }
+
+        fn enter_child(&mut self, steps: &[usize]) {
+            self.path.extend_from_slice(steps);
+        }
+
+        fn leave_child(&mut self, steps: &[usize]) {
+            self.path.truncate(self.path.len() - steps.len());
+        }
+    }
+
+    fn record(src: &str) -> Vec<String> {
+        let mut recorder = Recorder::default();
+        recorder.visit_expr(&parse_from_str(src).unwrap());
+        assert!(recorder.path.is_empty());
+        recorder.seen
+    }
+
+    #[test]
+    fn visits_let_bindings_in_scope_order() {
+        assert_eq!(
+            record("(let ((x 1) (y x)) (+ x y))"),
+            vec![
+                "/ (let ((x 1) (y x)) (+ x y))",
+                "/1/0/1 1",
+                "/1/0/0 binds x",
+                "/1/1/1 x",
+                "/1/1/0 binds y",
+                "/2 (+ x y)",
+                "/2/0 +",
+                "/2/1 x",
+                "/2/2 y",
+            ]
+        );
+    }
+
+    #[test]
+    fn visits_fn_parameters_before_the_body() {
+        assert_eq!(
+            record("(fn (a b) (if a '(b) b))"),
+            vec![
+                "/ (fn (a b) (if a '(b) b))",
+                "/1/0 binds a",
+                "/1/1 binds b",
+                "/2 (if a '(b) b)",
+                "/2/1 a",
+                "/2/2 '(b)",
+                "/2/2/0 b",
+                "/2/3 b",
+            ]
+        );
+    }
+
+    #[test]
+    fn paths_agree_with_query() {
+        let src = "(let ((f (fn (x) (* x 2)))) (f (if #t 1 2)))";
+        let expr = parse_from_str(src).unwrap();
+        let mut expected = Vec::new();
+        let mut stack = vec![(Vec::new(), &expr)];
+        while let Some((path, node)) = stack.pop() {
+            expected.push(format!("{} {}", Path(&path), node));
+            for (steps, child) in children(node).into_iter().rev() {
+                let mut path = path.clone();
+                path.extend(steps);
+                stack.push((path, child));
+            }
+        }
+        let visited: Vec<String> = record(src)
+            .into_iter()
+            .filter(|line| !line.contains(" binds "))
+            .collect();
+        assert_eq!(visited, expected);
+    }
+}
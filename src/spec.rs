@@ -0,0 +1,206 @@
+//! Finding the oxur examples in Markdown documents, for `oxur spec extract`.
+//!
+//! Examples are fenced code blocks whose info string starts with `oxur`, as
+//! in CommonMark: a fence is a line of at least three backticks or tildes,
+//! indented by at most three spaces, and it is closed by a line of at least
+//! as many of the same character with nothing after them. A block in another
+//! language is skipped whole, so a Markdown example that itself contains an
+//! ```` ```oxur ```` fence isn't checked, and a fence left open runs to the
+//! end of the document.
+
+use alloc::vec::Vec;
+
+use crate::error::{OxurError, Span};
+
+/// The code of an oxur block, and where it starts in its document.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Block<'a> {
+    pub offset: usize,
+    pub code: &'a str,
+}
+
+impl<'a> Block<'a> {
+    /// Move an error in this block's code to the same place in the document.
+    /// Spans are kept within the code, so an error at the end of the input
+    /// points after its last line rather than at the closing fence, and
+    /// errors without a span point at the start of the block.
+    pub fn locate(&self, mut error: OxurError) -> OxurError {
+        let end = self.code.trim_end().len();
+        let span = error.span.unwrap_or(Span { start: 0, end: 0 });
+        error.span = Some(Span {
+            start: self.offset + span.start.min(end),
+            end: self.offset + span.end.min(end),
+        });
+        error
+    }
+}
+
+/// The fence character, its length and the info string, if `line` is a fence.
+fn fence(line: &str) -> Option<(char, usize, &str)> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let unindented = line.trim_start_matches(' ');
+    if line.len() - unindented.len() > 3 {
+        return None;
+    }
+    let c = unindented
+        .chars()
+        .next()
+        .filter(|&c| c == '`' || c == '~')?;
+    let after = unindented.trim_start_matches(c);
+    let length = unindented.len() - after.len();
+    let info = after.trim();
+    // a backtick in the info string would make this inline code instead
+    if length < 3 || (c == '`' && info.contains('`')) {
+        return None;
+    }
+    Some((c, length, info))
+}
+
+/// The oxur blocks of a Markdown document, in order.
+pub fn oxur_blocks(text: &str) -> Vec<Block<'_>> {
+    let mut blocks = Vec::new();
+    // the character and length of the open fence, and where its code starts
+    // if it is an oxur block
+    let mut open: Option<(char, usize, Option<usize>)> = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        match (open, fence(line)) {
+            (None, Some((c, length, info))) => {
+                let start = match info.split_whitespace().next() {
+                    Some("oxur") => Some(offset + line.len()),
+                    _ => None,
+                };
+                open = Some((c, length, start));
+            }
+            (Some((c, length, start)), Some((found, found_length, "")))
+                if found == c && found_length >= length =>
+            {
+                if let Some(start) = start {
+                    blocks.push(Block {
+                        offset: start,
+                        code: &text[start..offset],
+                    });
+                }
+                open = None;
+            }
+            _ => {}
+        }
+        offset += line.len();
+    }
+    if let Some((_, _, Some(start))) = open {
+        blocks.push(Block {
+            offset: start,
+            code: &text[start..],
+        });
+    }
+    blocks
+}
+
+/// Every Markdown file under `path`, sorted, or `path` itself if it is a file.
+#[cfg(feature = "std")]
+pub fn find_markdown(path: &std::path::Path) -> std::io::Result<Vec<std::path::PathBuf>> {
+    fn walk(path: &std::path::Path, out: &mut Vec<std::path::PathBuf>) -> std::io::Result<()> {
+        if !path.is_dir() {
+            out.push(path.to_path_buf());
+            return Ok(());
+        }
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                walk(&path, out)?;
+            } else if matches!(path.extension(), Some(ext) if ext == "md") {
+                out.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut docs = Vec::new();
+    walk(path, &mut docs)?;
+    docs.sort();
+    Ok(docs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use crate::parser::limits::Parser;
+    use alloc::vec;
+
+    fn codes(text: &str) -> Vec<&str> {
+        oxur_blocks(text)
+            .into_iter()
+            .map(|block| block.code)
+            .collect()
+    }
+
+    #[test]
+    fn finds_oxur_blocks_only() {
+        let text =
+            "# Title\n\n```oxur\n(+ 1 2)\n```\n\n```rust\nfn main() {}\n```\n\n```\n3\n```\n";
+        let blocks = oxur_blocks(text);
+        assert_eq!(
+            blocks,
+            vec![Block {
+                offset: 17,
+                code: "(+ 1 2)\n"
+            }]
+        );
+        assert_eq!(&text[blocks[0].offset..][..7], "(+ 1 2)");
+    }
+
+    #[test]
+    fn the_info_string_may_say_more() {
+        let text = "```oxur title=\"sum\"\n1\n```\n```  oxur\n2\n```\n```oxurish\n3\n```\n";
+        assert_eq!(codes(text), vec!["1\n", "2\n"]);
+    }
+
+    #[test]
+    fn tildes_fence_too() {
+        let text = "~~~oxur\n1\n~~~\n~~~~ oxur\n2\n```\n~~~\n~~~~\n";
+        assert_eq!(codes(text), vec!["1\n", "2\n```\n~~~\n"]);
+    }
+
+    #[test]
+    fn longer_outer_fences_hold_shorter_ones() {
+        let text = "````markdown\n```oxur\nnot checked\n```\n````\n````oxur\n```\n1\n````\n";
+        assert_eq!(codes(text), vec!["```\n1\n"]);
+    }
+
+    #[test]
+    fn closing_fences_have_no_info_string() {
+        let text = "```oxur\n1\n```oxur\n2\n```\n";
+        assert_eq!(codes(text), vec!["1\n```oxur\n2\n"]);
+    }
+
+    #[test]
+    fn indented_fences() {
+        let text = "   ```oxur\n1\n   ```\n    ```oxur\n2\n    ```\n";
+        assert_eq!(codes(text), vec!["1\n"]);
+        // a backtick in the info string means the line isn't a fence
+        assert_eq!(codes("```oxur `x`\n1\n```\n"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn an_unclosed_fence_runs_to_the_end() {
+        assert_eq!(codes("text\n```oxur\n(+ 1\n2)"), vec!["(+ 1\n2)"]);
+        assert_eq!(codes("```rust\n```oxur\n1\n"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn errors_point_into_the_document() {
+        let text = "intro\n\n```oxur\n(+ 1\n   2\n```\n";
+        let block = oxur_blocks(text)[0];
+        let error = Parser::default().parse_all(block.code).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Syntax);
+        // the end of the input is after the `2`, not on the closing fence
+        let error = block.locate(error);
+        let span = error.span.unwrap();
+        assert_eq!(&text[..span.start], "intro\n\n```oxur\n(+ 1\n   2");
+        assert_eq!(span.start, span.end);
+
+        let error = block.locate(OxurError::new(ErrorCode::Eval, "no span"));
+        assert_eq!(error.span, Some(Span { start: 15, end: 15 }));
+    }
+}
@@ -0,0 +1,125 @@
+//! Replays every input in `fuzz/regressions` through the same checks as the
+//! fuzz targets, so the regressions run on stable as part of `cargo test`.
+//! Keep each check in step with its target in `fuzz/fuzz_targets`.
+
+use std::fs;
+use std::path::PathBuf;
+
+use oxur::error::ErrorCode;
+use oxur::parser::interp::{Env, Interpreter};
+use oxur::parser::limits::Limits;
+use oxur::parser::print::{Layout, Printer};
+use oxur::parser::{optimize, query, sexp};
+
+const QUERY_SOURCE: &str = "((if (= (+ 3 (/ 9 3)) (* 2 3)) * /) 456 '(:a #t (- 1 1)))";
+
+fn regressions(target: &str) -> Vec<(String, String)> {
+    let dir: PathBuf = [env!("CARGO_MANIFEST_DIR"), "fuzz", "regressions", target]
+        .iter()
+        .collect();
+    let mut inputs: Vec<(String, String)> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, fs::read_to_string(&path).unwrap())
+        })
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty(), "no regressions in {}", dir.display());
+    inputs
+}
+
+fn check_parse_expr(src: &str) {
+    if let Ok(expr) = sexp::parse_from_str(src) {
+        let printed = expr.to_string();
+        assert_eq!(sexp::parse_from_str(&printed).as_ref(), Ok(&expr));
+        for &layout in &[Layout::Fit, Layout::Compact, Layout::Expanded] {
+            let pretty = Printer { width: 20, layout }.print(&expr);
+            assert_eq!(sexp::parse_from_str(&pretty).as_ref(), Ok(&expr));
+        }
+    }
+}
+
+fn check_eval(src: &str) {
+    if let Ok(expr) = sexp::parse_from_str(src) {
+        let expected = sexp::eval_expression(expr.clone());
+        let optimized = optimize::optimize(expr.clone());
+        assert_eq!(sexp::eval_expression(optimized.clone()), expected);
+
+        // Closures are compared by how they print, since two evaluations
+        // never return the same one.
+        let interpreter = Interpreter::default();
+        let eval = |expr| {
+            interpreter
+                .eval(expr, &Env::default())
+                .map(|value| value.to_string())
+        };
+        let expected = eval(&expr);
+        // A folded tree is shallower, so it may finish where the original
+        // ran out of depth.
+        if !matches!(&expected, Err(error) if error.message.starts_with("evaluation nested")) {
+            assert_eq!(eval(&optimized), expected);
+        }
+    }
+}
+
+fn check_query_pattern(src: &str) {
+    if let Ok(pattern) = query::pattern_from_str(src) {
+        let expr = sexp::parse_from_str(QUERY_SOURCE).unwrap();
+        let _ = query::query(&pattern, &expr);
+    }
+}
+
+#[test]
+fn parse_expr_regressions() {
+    for (name, src) in regressions("parse_expr") {
+        println!("parse_expr/{}", name);
+        check_parse_expr(&src);
+    }
+}
+
+#[test]
+fn eval_regressions() {
+    for (name, src) in regressions("eval") {
+        println!("eval/{}", name);
+        check_eval(&src);
+    }
+}
+
+#[test]
+fn query_pattern_regressions() {
+    for (name, src) in regressions("query_pattern") {
+        println!("query_pattern/{}", name);
+        check_query_pattern(&src);
+    }
+}
+
+// The deep-nesting inputs are rejected by the pre-scan before any recursive
+// code runs; the at-depth-limit ones are the deepest input that gets past it,
+// so they are what actually exercises the parser, evaluator and printer.
+#[test]
+fn at_depth_limit_regressions_get_past_the_limits() {
+    let max_depth = Limits::default().max_depth;
+    for target in &["parse_expr", "eval", "query_pattern"] {
+        for (name, src) in regressions(target) {
+            let depth = src.matches('(').count();
+            let result = if *target == "query_pattern" {
+                query::pattern_from_str(&src).map(drop)
+            } else {
+                sexp::parse_from_str(&src).map(drop)
+            };
+            match name.as_str() {
+                "at-depth-limit" => {
+                    assert_eq!(depth, max_depth, "{}/{}", target, name);
+                    assert_eq!(result, Ok(()), "{}/{}", target, name);
+                }
+                "deep-nesting" => {
+                    let error = result.unwrap_err();
+                    assert_eq!(error.code, ErrorCode::LimitExceeded, "{}/{}", target, name);
+                }
+                _ => {}
+            }
+        }
+    }
+}